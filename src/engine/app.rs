@@ -1,65 +1,112 @@
 use super::{
     model::{self, DrawModel, Vertex}, 
     resources, 
-    textures::{texture, cubemap},
+    textures::{texture, cubemap, pbr, ibl},
     pipeline,
     resolution,
+    ssao,
+    postprocess::{tonemap, bloom, palette, antialias},
     camera,
+    camera_path,
+    input::{self, Action, InputEvent},
+    replay,
+    timestep,
+    culling,
     ecs,
     uniform
 };
 use winit::{
     event::*,
     event_loop::EventLoop,
-    keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowBuilder}
 };
 use wgpu::util::DeviceExt;
 use std::time::Duration;
 
+// finished frames go to the window, or into a texture when replaying
+// headless
+enum FrameTarget<'a> {
+    Surface(wgpu::Surface<'a>),
+    Texture(wgpu::Texture)
+}
+
 struct State<'a> {
-    surface: wgpu::Surface<'a>,
+    target: FrameTarget<'a>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-    window: &'a Window,
+    window: Option<&'a Window>,
     render_pipeline: wgpu::RenderPipeline,
+    pbr_pipeline: wgpu::RenderPipeline,
     skybox_pipeline: wgpu::RenderPipeline,
+    depth_pipeline: wgpu::RenderPipeline,
+    // depth is cleared once a frame, later cameras reset their viewport
+    // with these since they can overlap earlier ones
+    depth_clear_pipeline: wgpu::RenderPipeline,
+    multisampled_depth_clear_pipeline: Option<wgpu::RenderPipeline>,
     downscaler: resolution::ResolutionScalingPipeline,
-    camera: camera::Camera,
-    projection: camera::Projection,
-    camera_controller: camera::CameraController,
-    camera_uniform: uniform::CameraUniform,
-    camera_buffer: wgpu::Buffer,
-    camera_bind_group: wgpu::BindGroup,
+    // the configured internal resolution before matching the window or scaling
+    base_resolution: (u32, u32),
+    dynamic_resolution: Option<resolution::DynamicResolution>,
+    frame_timer: Option<resolution::FrameTimer>,
+    timestep: timestep::FixedTimestep,
+    // transforms as of the tick before the last, rendering blends from
+    // these to the current ones
+    previous_transforms: Vec<Option<ecs::transform::Transform>>,
+    // meshes drawn and left out by frustum culling over all cameras last frame
+    culling_stats: culling::CullingStats,
+    camera_controller: Box<dyn camera::CameraController>,
+    // C swaps this with the active one, so each keeps its own state
+    inactive_camera_controller: Box<dyn camera::CameraController>,
+    // the entity F frames, Tab moves through the entities with a model
+    selected_entity: usize,
+    camera_paths: Vec<camera_path::CameraPath>,
+    // the path P plays next, they're played in scene order
+    next_camera_path: usize,
+    camera_path_player: Option<camera_path::CameraPathPlayer>,
+    camera_path_recorder: Option<camera_path::CameraPathRecorder>,
+    input: input::Input,
+    // set by the exit action, the event loop checks it after each event
+    exit_requested: bool,
+    input_recorder: Option<replay::InputRecorder>,
     light_bind_group: wgpu::BindGroup,
+    environment_bind_group_layout: wgpu::BindGroupLayout,
+    environment: ibl::Environment,
+    fog_buffer: wgpu::Buffer,
+    environment_bind_group: wgpu::BindGroup,
+    sample_count: u32,
+    depth_mode: pipeline::DepthMode,
     depth_texture: texture::Texture,
+    multisampled_depth_texture: Option<texture::Texture>,
+    ssao: ssao::SsaoPipeline,
     world: ecs::ecs::World
 }
 
 impl<'a> State<'a> {
-    async fn new(window: &'a Window) -> State<'a> {
-        let size = window.inner_size();
-
+    // without a window frames are drawn into a texture of the given size
+    async fn new(window: Option<&'a Window>, size: winit::dpi::PhysicalSize<u32>) -> State<'a> {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             ..Default::default()
         });
 
-        let surface = instance.create_surface(window).unwrap();
+        let surface = window.map(|window| instance.create_surface(window).unwrap());
 
         let adapter = instance.request_adapter(
             &wgpu::RequestAdapterOptions{
                 power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: surface.as_ref(),
                 force_fallback_adapter: false
             }
         ).await.unwrap();
 
+        // timestamps are only for timing dynamic resolution, it's turned
+        // off without them
+        let timestamp_features = adapter.features() & resolution::FrameTimer::FEATURES;
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::PUSH_CONSTANTS,
+                required_features: wgpu::Features::PUSH_CONSTANTS | timestamp_features,
                 required_limits: wgpu::Limits{
                     max_push_constant_size: 128,
                     ..Default::default()
@@ -70,12 +117,19 @@ impl<'a> State<'a> {
             None
         ).await.unwrap();
 
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps.formats.iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-        
+        // headless frames are rgba so captures can be written out as they are
+        let (surface_format, alpha_mode) = match &surface {
+            Some(surface) => {
+                let surface_caps = surface.get_capabilities(&adapter);
+                let surface_format = surface_caps.formats.iter()
+                    .find(|f| f.is_srgb())
+                    .copied()
+                    .unwrap_or(surface_caps.formats[0]);
+                (surface_format, surface_caps.alpha_modes[0])
+            }
+            None => (wgpu::TextureFormat::Rgba8UnormSrgb, wgpu::CompositeAlphaMode::Opaque)
+        };
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -83,10 +137,31 @@ impl<'a> State<'a> {
             height: size.height,
             //present_mode: surface_caps.present_modes[0], // uncapped frames (if supported)
             present_mode: wgpu::PresentMode::Fifo, // force vsync
-            alpha_mode: surface_caps.alpha_modes[0],
+            alpha_mode,
             view_formats: vec![],
             desired_maximum_frame_latency: 2
         };
+        let target = match surface {
+            Some(surface) => FrameTarget::Surface(surface),
+            None => FrameTarget::Texture(create_frame_texture(&device, &config))
+        };
+
+        let settings = resources::load_settings("settings.json").await.unwrap_or_else(|e| {
+            log::warn!("Couldn't load settings, using defaults: {}", e);
+            Default::default()
+        });
+
+        let input_map = resources::load_input_map("input.json").await.unwrap_or_else(|e| {
+            log::warn!("Couldn't load input bindings, using defaults: {}", e);
+            Default::default()
+        });
+
+        let sample_count = pipeline::supported_sample_count(
+            &adapter,
+            &[texture::Texture::HDR_FORMAT, texture::Texture::DEPTH_FORMAT],
+            settings.msaa_samples()
+        );
+        let depth_mode = settings.depth_mode();
 
         let texture_bind_group_layout = device.create_bind_group_layout(
             &wgpu::BindGroupLayoutDescriptor {
@@ -107,6 +182,77 @@ impl<'a> State<'a> {
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ]
+        });
+
+        let pbr_texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true }
+            },
+            count: None
+        };
+        let pbr_sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None
+        };
+        // base color, metallic-roughness, normal, occlusion and emissive
+        // textures, their samplers in the same order and the material factors
+        let pbr_bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+            label: Some("Pbr Material Bind Group Layout"),
+            entries: &[
+                pbr_texture_entry(0),
+                pbr_texture_entry(1),
+                pbr_texture_entry(2),
+                pbr_texture_entry(3),
+                pbr_texture_entry(4),
+                pbr_sampler_entry(5),
+                pbr_sampler_entry(6),
+                pbr_sampler_entry(7),
+                pbr_sampler_entry(8),
+                pbr_sampler_entry(9),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
                 }
             ]
         });
@@ -134,27 +280,8 @@ impl<'a> State<'a> {
             ]
         });
 
-        let camera = camera::Camera::new(
-            (0.0, 5.0, 10.0),
-            cgmath::Deg(-90.0),
-            cgmath::Deg(-20.0)
-        );
-        let projection = camera::Projection::new(
-            config.width,
-            config.height,
-            cgmath::Deg(45.0),
-            0.1,
-            100.0
-        );
-        let camera_controller = camera::CameraController::new(4.0, 0.4);
-        let camera_uniform = uniform::CameraUniform::new();
-        let camera_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Camera Buffer"),
-                contents: bytemuck::cast_slice(&[camera_uniform]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
-            }
-        );
+        let camera_controller: Box<dyn camera::CameraController> = Box::new(camera::FlyController::new(4.0, 0.4));
+        let inactive_camera_controller: Box<dyn camera::CameraController> = Box::new(camera::OrbitController::new(0.4));
         let camera_bind_group_layout = device.create_bind_group_layout(
             &wgpu::BindGroupLayoutDescriptor {
             label: Some("Camera Bind Group Layout"),
@@ -171,20 +298,11 @@ impl<'a> State<'a> {
                 }
             ]
         });
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Camera Bind Group"),
-            layout: &camera_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding()
-                }
-            ]
-        });
 
+        let light_color = cgmath::vec3(0.2, 0.1, 0.2);
         let light_uniform = uniform::LightUniform::new(
             cgmath::vec3(1.0, 1.0, -2.0),
-            cgmath::vec3(0.2, 0.1, 0.2)
+            light_color
         );
         // If i want the uniform contents to change, i need to
         // make relevant struct values pub and add copy_dst
@@ -222,6 +340,56 @@ impl<'a> State<'a> {
             ]
         });
 
+        // irradiance and prefiltered cubemaps plus the brdf lut, generated
+        // from the scene skybox for image based lighting
+        let environment_texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true }
+            },
+            count: None
+        };
+        let environment_bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+            label: Some("Environment Bind Group Layout"),
+            entries: &[
+                environment_texture_entry(0, wgpu::TextureViewDimension::Cube),
+                environment_texture_entry(1, wgpu::TextureViewDimension::Cube),
+                environment_texture_entry(2, wgpu::TextureViewDimension::D2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                },
+                // screen space ambient occlusion, read with textureLoad
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false }
+                    },
+                    count: None
+                },
+                // fog is also read by the skybox
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ]
+        });
+
         // mat4x4 of f32 is 512 bits, or 64 bytes
         let model_push_range = wgpu::PushConstantRange {
             stages: wgpu::ShaderStages::VERTEX,
@@ -235,25 +403,64 @@ impl<'a> State<'a> {
                 bind_group_layouts: &[
                     &texture_bind_group_layout,
                     &camera_bind_group_layout,
-                    &light_bind_group_layout
+                    &light_bind_group_layout,
+                    &environment_bind_group_layout
                 ],
-                push_constant_ranges: &[
-                    model_push_range
-                ]
+                push_constant_ranges: std::slice::from_ref(&model_push_range)
             });
             let shader = wgpu::ShaderModuleDescriptor {
                 label: Some("Normal Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shader.wgsl").into())
+                source: wgpu::ShaderSource::Wgsl(format!(
+                    "{}\n{}",
+                    include_str!("shaders/fog.wgsl"),
+                    include_str!("shaders/shader.wgsl")
+                ).into())
             };
             pipeline::create_render_pipeline(
                 &device,
                 &render_pipeline_layout,
-                config.format,
+                texture::Texture::HDR_FORMAT,
                 Some(texture::Texture::DEPTH_FORMAT),
                 &[model::ModelVertex::desc()],
                 shader,
                 "Render Pipeline",
-                true
+                true,
+                depth_mode,
+                sample_count
+            )
+        };
+
+        let pbr_pipeline = {
+            let pbr_pipeline_layout =
+                device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Pbr Pipeline Layout"),
+                bind_group_layouts: &[
+                    &pbr_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &light_bind_group_layout,
+                    &environment_bind_group_layout
+                ],
+                push_constant_ranges: std::slice::from_ref(&model_push_range)
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Pbr Shader"),
+                source: wgpu::ShaderSource::Wgsl(format!(
+                    "{}\n{}",
+                    include_str!("shaders/fog.wgsl"),
+                    include_str!("shaders/pbr.wgsl")
+                ).into())
+            };
+            pipeline::create_render_pipeline(
+                &device,
+                &pbr_pipeline_layout,
+                texture::Texture::HDR_FORMAT,
+                Some(texture::Texture::DEPTH_FORMAT),
+                &[model::ModelVertex::desc()],
+                shader,
+                "Pbr Pipeline",
+                true,
+                depth_mode,
+                sample_count
             )
         };
 
@@ -263,107 +470,515 @@ impl<'a> State<'a> {
                 label: Some("Skybox Pipeline Layout"),
                 bind_group_layouts: &[
                     &skybox_bind_group_layout,
-                    &camera_bind_group_layout
+                    &camera_bind_group_layout,
+                    &environment_bind_group_layout
                 ],
                 push_constant_ranges: &[]
             });
             let shader = wgpu::ShaderModuleDescriptor {
                 label: Some("Skybox Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/sky.wgsl").into())
+                source: wgpu::ShaderSource::Wgsl(format!(
+                    "{}\n{}",
+                    include_str!("shaders/fog.wgsl"),
+                    include_str!("shaders/sky.wgsl")
+                ).into())
             };
             pipeline::create_render_pipeline(
                 &device,
                 &skybox_pipeline_layout,
-                config.format,
+                texture::Texture::HDR_FORMAT,
                 Some(texture::Texture::DEPTH_FORMAT),
-                &[cubemap::CubemapVertex::desc()],
+                &[],
                 shader,
                 "Skybox Pipeline",
-                false
+                false,
+                depth_mode,
+                sample_count
             )
         };
 
-        let resolution = (480, 270);
+        let depth_pipeline = {
+            let depth_pipeline_layout =
+                device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Depth Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: std::slice::from_ref(&model_push_range)
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some("Depth Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("shaders/depth.wgsl").into())
+            };
+            pipeline::create_depth_pipeline(
+                &device,
+                &depth_pipeline_layout,
+                texture::Texture::DEPTH_FORMAT,
+                &[model::ModelVertex::desc()],
+                shader,
+                "Depth Pipeline",
+                depth_mode,
+                1
+            )
+        };
+        let depth_clear_pipeline = pipeline::create_depth_clear_pipeline(
+            &device, None, texture::Texture::DEPTH_FORMAT, depth_mode, 1
+        );
+        let multisampled_depth_clear_pipeline = (sample_count > 1).then(|| pipeline::create_depth_clear_pipeline(
+            &device, Some(texture::Texture::HDR_FORMAT), texture::Texture::DEPTH_FORMAT, depth_mode, sample_count
+        ));
+
+        let upscale_mode = match settings.upscale_mode() {
+            Some(name) => resolution::UpscaleMode::from_name(name).unwrap_or_else(|| {
+                log::warn!("Unknown upscale mode: {}", name);
+                resolution::UpscaleMode::Stretch
+            }),
+            None => resolution::UpscaleMode::Stretch
+        };
+        let base_resolution = settings.internal_resolution();
+        let fitted_resolution = upscale_mode.internal_resolution(base_resolution, (config.width, config.height));
+        let dynamic_resolution = resolution::DynamicResolution::new(fitted_resolution, settings.dynamic_resolution());
+        let frame_timer = dynamic_resolution.as_ref().and_then(|_| resolution::FrameTimer::new(&device, &queue));
+        // nothing else tracks the gpu cost of a frame, so it stays off
+        let dynamic_resolution = match (dynamic_resolution, &frame_timer) {
+            (Some(_), None) => {
+                log::warn!("No timestamp queries on this adapter, dynamic resolution is disabled");
+                None
+            }
+            (dynamic_resolution, _) => dynamic_resolution
+        };
+        let resolution = dynamic_resolution.as_ref().map_or(fitted_resolution, |dynamic| dynamic.resolution());
 
         let depth_texture = texture::Texture::create_depth_texture(
-            &device, resolution, "Depth Texture");
+            &device, resolution, 1, "Depth Texture");
+        // with msaa on, the prepass depth is only used by ssao and the lit
+        // passes test against their own multisampled depth
+        let multisampled_depth_texture = (sample_count > 1).then(|| texture::Texture::create_depth_texture(
+            &device, resolution, sample_count, "Multisampled Depth Texture"
+        ));
+        let mut ssao = ssao::SsaoPipeline::new(
+            &device, &camera_bind_group_layout, &depth_texture.view, resolution
+        );
 
-        let downscaler = resolution::ResolutionScalingPipeline::new(
+        let mut downscaler = resolution::ResolutionScalingPipeline::new(
             &device,
             &config,
-            resolution
+            resolution,
+            sample_count,
+            upscale_mode
         );
 
-        let world = resources::load_scene(
-            "scenes/test.json", &device, &queue, &texture_bind_group_layout, &skybox_bind_group_layout
+        let scene = resources::load_scene(
+            "scenes/test.json",
+            &device,
+            &queue,
+            &texture_bind_group_layout,
+            &skybox_bind_group_layout,
+            &pbr_bind_group_layout,
+            &camera_bind_group_layout
         ).await.unwrap();
+        let world = scene.world;
+        for camera in world.borrow_component_vec::<camera::CameraComponent>().unwrap().iter_mut().flatten() {
+            camera.projection.set_depth_mode(depth_mode);
+        }
+
+        let settings = settings.with_scene_overrides(&scene.post_processing);
+
+        // the palette effect gets its files here rather than in configure,
+        // a missing file keeps the built in palette or a flat dither tile
+        let noise = match resources::load_linear_texture("dither/blue_noise.png", &device, &queue).await {
+            Ok(noise) => noise,
+            Err(e) => {
+                log::warn!("Couldn't load blue noise: {}", e);
+                texture::Texture::from_color(&device, &queue, [128, 128, 128, 255], false, "Blue Noise Texture").unwrap()
+            }
+        };
+        let mut palette_effect = palette::PaletteEffect::new(
+            &device, &queue, downscaler.post_process.input_layout(), noise
+        );
+        if let Some(path) = settings.effect(palette::PaletteEffect::NAME)["path"].as_str() {
+            match resources::load_palette(path).await {
+                Ok(colors) => palette_effect.set_palette(&device, &queue, &colors),
+                Err(e) => log::warn!("Couldn't load palette {}: {}", path, e)
+            }
+        }
+
+        // effects run in the order they are registered, hdr effects go
+        // before the tone mapping and ldr ones after it, anti aliasing
+        // goes before the palette so it doesn't undo the quantization
+        downscaler.post_process.register(&device, Box::new(bloom::BloomEffect::new(&device)));
+        let tone_map_effect = tonemap::ToneMapEffect::new(&device, downscaler.post_process.input_layout());
+        downscaler.post_process.register(&device, Box::new(tone_map_effect));
+        downscaler.post_process.register(&device, Box::new(antialias::AntiAliasEffect::new(&device, &queue)));
+        downscaler.post_process.register(&device, Box::new(palette_effect));
+        downscaler.post_process.configure(&settings);
+        ssao.configure(settings.ssao());
+        downscaler.configure(&device, settings.resolution());
+
+        // scenes without a skybox keep the old flat ambient term
+        let environment = {
+            let skyboxes = world.borrow_component_vec::<cubemap::CubemapComponent>();
+            match skyboxes.as_ref().and_then(|skyboxes| skyboxes.iter().flatten().next()) {
+                Some(skybox) => ibl::Environment::from_cubemap(&device, &queue, &skybox.cubemap),
+                None => ibl::Environment::from_color(&device, &queue, light_color * 0.1)
+            }
+        };
+        let fog_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+            label: Some("Fog Buffer"),
+            contents: bytemuck::cast_slice(&[scene.fog]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+        let environment_bind_group = environment.create_bind_group(
+            &device, &environment_bind_group_layout, ssao.occlusion_view(), &fog_buffer
+        );
 
-        Self{
+        let state = Self{
             window,
-            surface,
+            target,
             device,
             queue,
             config,
             size,
             render_pipeline,
+            pbr_pipeline,
             skybox_pipeline,
+            depth_pipeline,
+            depth_clear_pipeline,
+            multisampled_depth_clear_pipeline,
             downscaler,
-            camera,
-            projection,
+            base_resolution,
+            dynamic_resolution,
+            frame_timer,
+            timestep: timestep::FixedTimestep::new(settings.simulation()),
+            previous_transforms: Vec::new(),
+            culling_stats: Default::default(),
             camera_controller,
-            camera_uniform,
-            camera_buffer,
-            camera_bind_group,
+            inactive_camera_controller,
+            selected_entity: 0,
+            camera_paths: scene.camera_paths,
+            next_camera_path: 0,
+            camera_path_player: None,
+            camera_path_recorder: None,
+            input: input::Input::new(input_map),
+            exit_requested: false,
+            input_recorder: None,
             light_bind_group,
+            environment_bind_group_layout,
+            environment,
+            fog_buffer,
+            environment_bind_group,
+            sample_count,
+            depth_mode,
             depth_texture,
+            multisampled_depth_texture,
+            ssao,
             world
-        }
+        };
+        state.resize_cameras();
+        state
     }
 
     pub fn window(&self) -> &Window {
-        &self.window
+        self.window.expect("headless state has no window")
     }
 
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
+            // winit can report the size we already have
+            if new_size != self.size {
+                if let Some(recorder) = self.input_recorder.as_mut() {
+                    recorder.record(replay::RecordedEvent::Resize(new_size.width, new_size.height));
+                }
+            }
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.projection.resize(new_size.width, new_size.height);
-            //self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "Depth Texture");
-            self.surface.configure(&self.device, &self.config);
+            self.reconfigure_target();
+
+            let surface_size = (new_size.width, new_size.height);
+            self.downscaler.set_surface_size(&self.device, surface_size);
+            let base = self.downscaler.mode().internal_resolution(self.base_resolution, surface_size);
+            let resolution = match self.dynamic_resolution.as_mut() {
+                Some(dynamic) => dynamic.set_base(base),
+                None => base
+            };
+            if resolution != self.downscaler.resolution() {
+                self.set_resolution(resolution);
+            }
+            self.resize_cameras();
+        }
+    }
+
+    // replays draw at the fitted resolution, the gpu timing behind dynamic
+    // resolution would pick different sizes on every run
+    fn disable_dynamic_resolution(&mut self) {
+        if self.dynamic_resolution.take().is_some() {
+            log::info!("Dynamic resolution is off while replaying");
+        }
+        self.frame_timer = None;
+        let surface_size = (self.size.width, self.size.height);
+        let base = self.downscaler.mode().internal_resolution(self.base_resolution, surface_size);
+        if base != self.downscaler.resolution() {
+            self.set_resolution(base);
+        }
+    }
+
+    // a lost or outdated surface only needs configuring again at the same
+    // size, that isn't a resize and doesn't end up in recordings
+    fn reconfigure_target(&mut self) {
+        match &mut self.target {
+            FrameTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+            FrameTarget::Texture(texture) => *texture = create_frame_texture(&self.device, &self.config)
+        }
+    }
+
+    // everything drawn at the internal resolution is recreated together
+    fn set_resolution(&mut self, resolution: (u32, u32)) {
+        self.depth_texture = texture::Texture::create_depth_texture(
+            &self.device, resolution, 1, "Depth Texture");
+        self.multisampled_depth_texture = (self.sample_count > 1).then(|| texture::Texture::create_depth_texture(
+            &self.device, resolution, self.sample_count, "Multisampled Depth Texture"
+        ));
+        self.ssao.resize(&self.device, &self.depth_texture.view, resolution);
+        self.downscaler.resize(&self.device, resolution);
+        self.environment_bind_group = self.environment.create_bind_group(
+            &self.device, &self.environment_bind_group_layout, self.ssao.occlusion_view(), &self.fog_buffer
+        );
+        self.resize_cameras();
+    }
+
+    fn resize_cameras(&self) {
+        let view_size = self.downscaler.projection_size();
+        let target_size = self.downscaler.resolution();
+        let mut cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+        for camera in cameras.iter_mut().flatten() {
+            camera.resize(view_size, target_size);
         }
     }
 
+    fn switch_camera_controller(&mut self) {
+        std::mem::swap(&mut self.camera_controller, &mut self.inactive_camera_controller);
+        let mut cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+        if let Some(active) = camera::active_camera(&mut cameras) {
+            self.camera_controller.attach(&active.camera);
+        }
+        log::info!("Camera controller: {}", self.camera_controller.name());
+    }
+
+    // next entity after the selected one that has a model, wrapping around
+    fn select_next_entity(&mut self) {
+        let models = self.world.borrow_component_vec::<model::Model>().unwrap();
+        let count = models.len();
+        if let Some(entity) = (1..=count)
+            .map(|offset| (self.selected_entity + offset) % count)
+            .find(|entity| models[*entity].is_some())
+        {
+            self.selected_entity = entity;
+            log::info!("Selected entity {}", entity);
+        }
+    }
+
+    fn frame_selected_entity(&mut self) {
+        let bounds = {
+            let models = self.world.borrow_component_vec::<model::Model>().unwrap();
+            let transforms = self.world.borrow_component_vec::<ecs::transform::Transform>().unwrap();
+            let (Some(Some(model)), Some(Some(transform))) =
+                (models.get(self.selected_entity), transforms.get(self.selected_entity)) else {
+                return;
+            };
+            model.bounds().transform(&transform.mat4())
+        };
+        let mut cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+        if let Some(active) = camera::active_camera(&mut cameras) {
+            let distance = active.projection.fit_distance(bounds.radius());
+            active.projection.fit_orthographic_size(bounds.radius());
+            self.camera_controller.frame(&mut active.camera, bounds.center(), distance);
+        }
+    }
+
+    // plays the next path or stops the one playing, the controller picks up
+    // wherever the path left the camera
+    fn toggle_camera_path_playback(&mut self) {
+        if let Some(player) = self.camera_path_player.take() {
+            log::info!("Stopped camera path {}", player.name());
+            let mut cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+            if let Some(active) = camera::active_camera(&mut cameras) {
+                self.camera_controller.attach(&active.camera);
+            }
+            return;
+        }
+        if self.camera_paths.is_empty() {
+            log::warn!("The scene has no camera paths");
+            return;
+        }
+        let path = self.camera_paths[self.next_camera_path].clone();
+        self.next_camera_path = (self.next_camera_path + 1) % self.camera_paths.len();
+        log::info!("Playing camera path {}", path.name);
+        self.camera_path_player = Some(camera_path::CameraPathPlayer::new(path));
+    }
+
+    // stopping a recording saves it and makes it playable straight away
+    fn toggle_camera_path_recording(&mut self) {
+        let Some(recorder) = self.camera_path_recorder.take() else {
+            log::info!("Recording camera path");
+            self.camera_path_recorder = Some(camera_path::CameraPathRecorder::new());
+            return;
+        };
+        let mut cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+        let Some(active) = camera::active_camera(&mut cameras) else {
+            return;
+        };
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        let path = recorder.finish(&format!("recording_{}", seconds), &active.camera, &active.projection);
+        match resources::save_camera_path(&path) {
+            Ok(file) => log::info!("Saved camera path to {}", file.display()),
+            Err(e) => log::warn!("Couldn't save camera path: {}", e)
+        }
+        self.camera_paths.push(path);
+    }
+
+    // editor style views, the orbit controller keeps circling its target
+    // since it places the camera from the rotation every frame
+    fn set_camera_view(&mut self, action: Action) {
+        let mut cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+        let Some(active) = camera::active_camera(&mut cameras) else {
+            return;
+        };
+        let (yaw, pitch) = match action {
+            Action::ToggleProjection => {
+                let kind = active.projection.toggle_kind();
+                log::info!("Camera projection: {:?}", kind);
+                return;
+            }
+            // front looks down -z, side down -x and top straight down
+            Action::ViewFront => (-90.0, 0.0),
+            Action::ViewSide => (180.0, 0.0),
+            _ => (-90.0, -90.0)
+        };
+        active.camera.set_rotation(cgmath::Deg(yaw), cgmath::Deg(pitch));
+        active.projection.kind = camera::ProjectionKind::Orthographic;
+    }
+
+    // true when the event was bound to something
     fn input(&mut self, event: &WindowEvent) -> bool {
+        let events = self.input.window_event(event);
+        for event in &events {
+            self.handle_input_event(*event);
+        }
+        !events.is_empty()
+    }
+
+    fn mouse_motion(&mut self, delta: (f64, f64)) {
+        for event in self.input.mouse_motion(delta) {
+            self.handle_input_event(event);
+        }
+    }
+
+    fn handle_input_event(&mut self, event: InputEvent) {
+        if let Some(recorder) = self.input_recorder.as_mut() {
+            recorder.record(replay::RecordedEvent::Input(event));
+        }
         match event {
-            WindowEvent::KeyboardInput {
-                event: KeyEvent {
-                    physical_key: PhysicalKey::Code(key),
-                    state,
-                    ..
-                },
-                ..
-            } => {
-                self.camera_controller.process_keyboard(*key, *state)
+            InputEvent::Axis(axis, value) => self.camera_controller.process_axis(axis, value),
+            InputEvent::Action(action, pressed) => {
+                if !(pressed && self.handle_action(action)) {
+                    self.camera_controller.process_action(action, pressed);
+                }
             }
-            _ => {
-                false
+        }
+    }
+
+    // actions that fire once on press, everything else goes to the
+    // camera controller
+    fn handle_action(&mut self, action: Action) -> bool {
+        match action {
+            Action::SwitchController => self.switch_camera_controller(),
+            Action::SelectNext => self.select_next_entity(),
+            Action::FrameSelected => self.frame_selected_entity(),
+            Action::PlayPath => self.toggle_camera_path_playback(),
+            Action::RecordPath => self.toggle_camera_path_recording(),
+            Action::ViewFront | Action::ViewSide | Action::ViewTop | Action::ToggleProjection => {
+                self.set_camera_view(action);
             }
+            Action::NextToneMapping | Action::ExposureDown | Action::ExposureUp => {
+                if let Some(tone_map) = self.downscaler.post_process.effect_mut(tonemap::ToneMapEffect::NAME) {
+                    let mode = tone_map.parameter("mode").unwrap_or(0.0);
+                    let exposure = tone_map.parameter("exposure").unwrap_or(1.0);
+                    match action {
+                        Action::NextToneMapping => {
+                            let tone_mapping = tonemap::ToneMapping::from_mode(mode).next();
+                            tone_map.set_parameter("mode", tone_mapping as u32 as f32);
+                        }
+                        Action::ExposureDown => tone_map.set_parameter("exposure", exposure / 1.25),
+                        _ => tone_map.set_parameter("exposure", exposure * 1.25)
+                    }
+                }
+            }
+            Action::ToggleSsao => {
+                let enabled = self.ssao.toggle();
+                log::info!("Ssao enabled: {}", enabled);
+            }
+            Action::NextUpscaleFilter => {
+                let filter = self.downscaler.filter().next();
+                self.downscaler.set_filter(&self.device, filter);
+                log::info!("Upscale filter: {:?}", filter);
+            }
+            Action::ToggleEffect(index) => {
+                if let Some((name, enabled)) = self.downscaler.post_process.toggle(index) {
+                    log::info!("Post processing effect {} enabled: {}", name, enabled);
+                }
+            }
+            Action::Rebind => self.input.start_rebinding(),
+            Action::Exit => self.exit_requested = true,
+            _ => return false
         }
+        true
     }
 
     fn update(&mut self, dt: Duration) {
-        self.camera_controller.update_camera(&mut self.camera, dt);
-        self.camera_uniform.update_view_projection(&self.camera, &self.projection);
-        self.queue.write_buffer(
-            &self.camera_buffer,
-            0,
-            bytemuck::cast_slice(&[self.camera_uniform])
-        );
+        // everything handled since the last update belongs to this frame
+        if let Some(recorder) = self.input_recorder.as_mut() {
+            recorder.end_frame(dt);
+        }
+
+        let frame_time = self.frame_timer.as_mut().and_then(|timer| timer.take(&self.device));
+        if let Some(resolution) = frame_time.and_then(|frame_time| self.dynamic_resolution.as_mut()?.update(frame_time)) {
+            log::info!("Internal resolution: {}x{}", resolution.0, resolution.1);
+            self.set_resolution(resolution);
+        }
+
+        let mut cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+        if let Some(active) = camera::active_camera(&mut cameras) {
+            match self.camera_path_player.as_mut() {
+                Some(player) => if !player.update(&mut active.camera, &mut active.projection, dt) {
+                    log::info!("Finished camera path {}", player.name());
+                    self.camera_path_player = None;
+                    self.camera_controller.attach(&active.camera);
+                }
+                None => self.camera_controller.update_camera(&mut active.camera, &mut active.projection, dt)
+            }
+            if let Some(recorder) = self.camera_path_recorder.as_mut() {
+                recorder.update(&active.camera, &active.projection, dt);
+            }
+        }
+        for camera in cameras.iter_mut().flatten() {
+            camera.update_buffer(&self.queue);
+        }
+        drop(cameras);
 
-        /* example of modifying componenets by frame */
+        for _ in 0..self.timestep.advance(dt) {
+            self.tick(self.timestep.tick());
+        }
+    }
+
+    // the simulation, always stepped by the same dt. the camera stays in
+    // update since it follows input and would lag a tick behind otherwise
+    fn tick(&mut self, dt: Duration) {
         let transforms = &mut self.world.borrow_component_vec::<ecs::transform::Transform>().unwrap();
+        self.previous_transforms.clone_from(transforms);
+
+        /* example of modifying componenets by tick */
         for transform in transforms.iter_mut().filter_map(|f| f.as_mut()) {
             if transform.scale == 2.0 {
                 transform.rotation.y += 15.0 * dt.as_secs_f32();
@@ -371,93 +986,321 @@ impl<'a> State<'a> {
         }
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+    // entities without a previous tick yet are drawn where they are
+    fn interpolated_transforms(&self) -> Vec<Option<ecs::transform::Transform>> {
+        let alpha = self.timestep.alpha();
+        let transforms = self.world.borrow_component_vec::<ecs::transform::Transform>().unwrap();
+        transforms.iter()
+            .enumerate()
+            .map(|(entity, transform)| {
+                let transform = transform.as_ref()?;
+                Some(match self.previous_transforms.get(entity) {
+                    Some(Some(previous)) => previous.lerp(transform, alpha),
+                    _ => transform.clone()
+                })
+            })
+            .collect()
+    }
 
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let (output, view) = match &self.target {
+            FrameTarget::Surface(surface) => {
+                let output = surface.get_current_texture()?;
+                let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(output), view)
+            }
+            FrameTarget::Texture(texture) => (None, texture.create_view(&wgpu::TextureViewDescriptor::default()))
+        };
 
         let mut encoder = 
             self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder")
         });
+        if let Some(timer) = self.frame_timer.as_mut() {
+            timer.begin(&mut encoder);
+        }
 
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: self.downscaler.view(),
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        a: 1.0
+        // we need to borrow the relevant components before we can use them for draw calls
+        let transforms = &self.interpolated_transforms();
+        let models = &self.world.borrow_component_vec::<model::Model>().unwrap();
+        let textures = self.world.borrow_component_vec::<texture::Material>();
+        let pbr_materials = self.world.borrow_component_vec::<pbr::PbrMaterialComponent>();
+
+        let skybox = self.world.borrow_component_vec::<cubemap::CubemapComponent>().unwrap();
+        let cameras = self.world.borrow_component_vec::<camera::CameraComponent>().unwrap();
+        let resolution = self.downscaler.resolution();
+        let mut culling_stats = culling::CullingStats::default();
+
+        // depth and occlusion are cleared once for all the cameras, each
+        // camera only touches its own viewport after that
+        let depth_textures = std::iter::once(&self.depth_texture).chain(self.multisampled_depth_texture.as_ref());
+        for depth_texture in depth_textures {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Clear Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.depth_mode.far_depth()),
+                        store: wgpu::StoreOp::Store
+                    }),
+                    stencil_ops: None
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None
+            });
+        }
+        self.ssao.clear(&mut encoder);
+
+        // every camera renders the whole scene into its viewport, with its
+        // own prepass and ssao since both depend on the camera
+        for (index, camera) in camera::cameras_by_priority(&cameras).into_iter().enumerate() {
+            let (x, y, width, height) = camera.viewport_rect(resolution);
+            let scissor = (x as u32, y as u32, width as u32, height as u32);
+
+            // every pass below only draws what this camera can see
+            let frustum = culling::Frustum::from_matrix(&camera.view_projection());
+            let visible = culling::visible_meshes(&frustum, models, transforms, &mut culling_stats);
+
+            // depth prepass, ssao needs the depth of the whole frame before
+            // the lit shaders read its result
+            let mut depth_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Depth Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store
                     }),
-                    store: wgpu::StoreOp::Store
+                    stencil_ops: None
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None
+            });
+            depth_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            depth_pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+            // the viewport may still hold the depth of an earlier camera
+            if index > 0 {
+                depth_pass.set_pipeline(&self.depth_clear_pipeline);
+                depth_pass.draw(0..3, 0..1);
+            }
+            depth_pass.set_pipeline(&self.depth_pipeline);
+            depth_pass.set_bind_group(0, &camera.bind_group, &[]);
+            let iter = visible.iter()
+                .zip(transforms.iter())
+                .filter(|(meshes, _)| !meshes.is_empty())
+                .filter_map(|(meshes, transform)| Some((meshes, transform.as_ref()?)));
+            for (meshes, transform) in iter {
+                let model_mat = ecs::transform::ModelPush::from_transform(transform);
+                depth_pass.set_push_constants(
+                    wgpu::ShaderStages::VERTEX,
+                    0,
+                    bytemuck::cast_slice(&[model_mat])
+                );
+                for mesh in meshes {
+                    depth_pass.draw_mesh(mesh);
                 }
-            })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store
+            }
+            drop(depth_pass);
+
+            self.ssao.render(&self.queue, &mut encoder, &camera.bind_group, (x, y, width, height));
+
+            // later cameras draw over the earlier ones inside their viewport
+            let color_load = if index == 0 {
+                wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.0,
+                    g: 0.0,
+                    b: 0.0,
+                    a: 1.0
+                })
+            } else {
+                wgpu::LoadOp::Load
+            };
+
+            // the prepass depth can be reused unless the lit passes are multisampled
+            let depth_view = self.multisampled_depth_texture.as_ref()
+                .map_or(&self.depth_texture.view, |depth_texture| &depth_texture.view);
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.downscaler.view(),
+                    resolve_target: self.downscaler.resolve_target(),
+                    ops: wgpu::Operations {
+                        load: color_load,
+                        store: wgpu::StoreOp::Store
+                    }
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store
+                    }),
+                    stencil_ops: None
                 }),
-                stencil_ops: None
-            }),
-            occlusion_query_set: None,
-            timestamp_writes: None
-        });
+                occlusion_query_set: None,
+                timestamp_writes: None
+            });
 
-        // rendering skybox
-        render_pass.set_pipeline(&self.skybox_pipeline);
-        let skybox = &mut self.world.borrow_component_vec::<cubemap::CubemapComponent>().unwrap();
-        for sky in skybox.iter_mut().filter_map(|f| f.as_mut()) {
-            render_pass.set_vertex_buffer(0, sky.vertices.slice(..));
-            render_pass.set_bind_group(0, &sky.bind_group, &[]);
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.draw(0..36, 0..1);
-        }
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            render_pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+            if let Some(depth_clear_pipeline) = self.multisampled_depth_clear_pipeline.as_ref().filter(|_| index > 0) {
+                render_pass.set_pipeline(depth_clear_pipeline);
+                render_pass.draw(0..3, 0..1);
+            }
 
-        // rendering standard entities
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-        render_pass.set_bind_group(2, &self.light_bind_group, &[]);
-        // we need to borrow the relevant components before we can use them for draw calls
-        let transforms = &mut self.world.borrow_component_vec::<ecs::transform::Transform>().unwrap();
-        let models = &mut self.world.borrow_component_vec::<model::Model>().unwrap();
-        let textures = &mut self.world.borrow_component_vec::<texture::Material>().unwrap();
-        let zip = models.iter_mut()
-            .zip(transforms.iter_mut())
-            .zip(textures.iter_mut());
-        let iter = zip.filter_map(|((model, transform), texture)| {
-            Some((model.as_mut()?, transform.as_mut()?, texture.as_mut()?))
-        });
-        for (model, transform, texture) in iter {
-            let model_mat = ecs::transform::ModelPush::from_transform(transform);
-            render_pass.set_push_constants(
-                wgpu::ShaderStages::VERTEX,
-                0,
-                bytemuck::cast_slice(&[model_mat])
-            );
-            render_pass.set_bind_group(0, &texture.bind_group, &[]);
-            render_pass.draw_model(model);
-        }
+            // rendering skybox
+            render_pass.set_pipeline(&self.skybox_pipeline);
+            for sky in skybox.iter().filter_map(|f| f.as_ref()) {
+                render_pass.set_bind_group(0, &sky.bind_group, &[]);
+                render_pass.set_bind_group(1, &camera.bind_group, &[]);
+                render_pass.set_bind_group(2, &self.environment_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
 
-        // gotta drop the render_pass here since it borrows the encoder and we need it back
-        drop(render_pass);
+            // rendering standard entities
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(1, &camera.bind_group, &[]);
+            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+            render_pass.set_bind_group(3, &self.environment_bind_group, &[]);
+            if let Some(textures) = textures.as_ref() {
+                let zip = visible.iter()
+                    .zip(transforms.iter())
+                    .zip(textures.iter())
+                    .filter(|((meshes, _), _)| !meshes.is_empty());
+                let iter = zip.filter_map(|((meshes, transform), texture)| {
+                    Some((meshes, transform.as_ref()?, texture.as_ref()?))
+                });
+                for (meshes, transform, texture) in iter {
+                    let model_mat = ecs::transform::ModelPush::from_transform(transform);
+                    render_pass.set_push_constants(
+                        wgpu::ShaderStages::VERTEX,
+                        0,
+                        bytemuck::cast_slice(&[model_mat])
+                    );
+                    render_pass.set_bind_group(0, &texture.bind_group, &[]);
+                    for mesh in meshes {
+                        render_pass.draw_mesh(mesh);
+                    }
+                }
+            }
+
+            // rendering pbr entities, every mesh picks its own material
+            render_pass.set_pipeline(&self.pbr_pipeline);
+            if let Some(pbr_materials) = pbr_materials.as_ref() {
+                let zip = visible.iter()
+                    .zip(transforms.iter())
+                    .zip(pbr_materials.iter())
+                    .filter(|((meshes, _), _)| !meshes.is_empty());
+                let iter = zip.filter_map(|((meshes, transform), materials)| {
+                    Some((meshes, transform.as_ref()?, materials.as_ref()?))
+                });
+                for (meshes, transform, materials) in iter {
+                    let model_mat = ecs::transform::ModelPush::from_transform(transform);
+                    render_pass.set_push_constants(
+                        wgpu::ShaderStages::VERTEX,
+                        0,
+                        bytemuck::cast_slice(&[model_mat])
+                    );
+                    for mesh in meshes {
+                        render_pass.set_bind_group(0, &materials.material(mesh.material).bind_group, &[]);
+                        render_pass.draw_mesh(mesh);
+                    }
+                }
+            }
+
+            // gotta drop the render_pass here since it borrows the encoder and we need it back
+            drop(render_pass);
+        }
+        if culling_stats != self.culling_stats {
+            log::debug!("Frustum culled {} of {} meshes", culling_stats.culled, culling_stats.meshes);
+            self.culling_stats = culling_stats;
+        }
 
         // render downscaled frame to surface texture
-        self.downscaler.upscale(&mut encoder, &view);
+        self.downscaler.upscale(&self.queue, &mut encoder, &view);
 
+        if let Some(timer) = self.frame_timer.as_mut() {
+            timer.end(&mut encoder);
+        }
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        if let Some(timer) = self.frame_timer.as_mut() {
+            timer.submitted();
+        }
+        if let Some(output) = output {
+            output.present();
+        }
         
         Ok(())
     }
+
+    // feeds a recorded frame through the same path live input takes
+    fn replay_frame(&mut self, frame: &replay::RecordedFrame) {
+        for event in &frame.events {
+            match *event {
+                replay::RecordedEvent::Input(event) => self.handle_input_event(event),
+                // a window follows its own size, only headless replays resize
+                replay::RecordedEvent::Resize(width, height) => if self.window.is_none() {
+                    self.resize(winit::dpi::PhysicalSize::new(width, height));
+                }
+            }
+        }
+        self.update(frame.dt);
+    }
+
+    fn capture_frame(&self, file: &std::path::Path) -> anyhow::Result<()> {
+        match &self.target {
+            FrameTarget::Texture(texture) => replay::save_texture(&self.device, &self.queue, texture, file),
+            FrameTarget::Surface(_) => anyhow::bail!("Only headless frames can be captured")
+        }
+    }
+}
+
+fn create_frame_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Frame Texture"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[]
+    })
+}
+
+fn save_input_recording(recorder: replay::InputRecorder, file: &std::path::Path) {
+    match recorder.finish().save(file) {
+        Ok(()) => log::info!("Saved input recording to {}", file.display()),
+        Err(e) => log::error!("Couldn't save input recording: {}", e)
+    }
 }
 
 pub async fn run() {
     env_logger::init();
+    let options = replay::ReplayOptions::from_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        log::error!("{}", e);
+        std::process::exit(1);
+    });
+    let recording = options.replay.as_ref().map(|file| {
+        replay::InputRecording::load(file).unwrap_or_else(|e| {
+            log::error!("Couldn't load input recording {}: {}", file.display(), e);
+            std::process::exit(1);
+        })
+    });
+    // the options only allow headless with a recording to replay
+    if options.headless {
+        run_headless(recording.unwrap(), &options).await;
+        return;
+    }
+    let mut input_replay = recording.map(replay::InputReplay::new);
+
     let event_loop = EventLoop::new().unwrap();
     let window = WindowBuilder::new()
         .with_title("WGPU Gaming")
@@ -467,32 +1310,30 @@ pub async fn run() {
     window.set_cursor_visible(false);
     window.set_cursor_grab(winit::window::CursorGrabMode::Confined).unwrap();
 
-    let mut state = State::new(&window).await;
+    let mut state = State::new(Some(&window), window.inner_size()).await;
+    if input_replay.is_some() {
+        state.disable_dynamic_resolution();
+    }
+    if options.record.is_some() {
+        state.input_recorder = Some(replay::InputRecorder::new((state.size.width, state.size.height)));
+    }
     let mut last_render_time = std::time::Instant::now();
 
     event_loop.run(move |event, control_flow| {
         match event {
+            // live input is ignored while a recording plays
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
-            } => {
-                state.camera_controller.process_mouse(delta.0, delta.1);
+            } if input_replay.is_none() => {
+                state.mouse_motion(delta);
             }
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == state.window().id() => if !state.input(event) {
+            } if window_id == state.window().id() && (input_replay.is_some() || !state.input(event)) => {
                 match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        event:
-                            KeyEvent {
-                                state: ElementState::Pressed,
-                                physical_key: PhysicalKey::Code(KeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => control_flow.exit(),
+                    WindowEvent::CloseRequested => control_flow.exit(),
                     WindowEvent::Resized(physical_size) => {
                         state.resize(*physical_size);
                     }
@@ -503,12 +1344,20 @@ pub async fn run() {
                         let dt = now - last_render_time;
                         last_render_time = now;
 
-                        state.update(dt);
+                        match input_replay.as_mut().map(replay::InputReplay::next_frame) {
+                            Some(Some(frame)) => state.replay_frame(&frame),
+                            Some(None) => {
+                                log::info!("Replay finished, back to live input");
+                                input_replay = None;
+                                state.update(dt);
+                            }
+                            None => state.update(dt)
+                        }
                         match state.render() {
                             Ok(_) => {}
                             Err (
                                 wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated
-                            ) => state.resize(state.size),
+                            ) => state.reconfigure_target(),
                             Err(wgpu::SurfaceError::OutOfMemory) => {
                                 log::error!("Out of Memory!");
                                 control_flow.exit();
@@ -521,7 +1370,52 @@ pub async fn run() {
                     _ => {}
                 }
             }
+            Event::LoopExiting => {
+                if let (Some(recorder), Some(file)) = (state.input_recorder.take(), options.record.as_ref()) {
+                    save_input_recording(recorder, file);
+                }
+            }
             _ => {}
         }
+        if state.exit_requested {
+            control_flow.exit();
+        }
     }).unwrap();
 }
+
+// replays as fast as frames can be drawn, every frame gets the recorded dt
+// so the world ends up exactly where it did when recording
+async fn run_headless(recording: replay::InputRecording, options: &replay::ReplayOptions) {
+    let size = winit::dpi::PhysicalSize::new(recording.size.0, recording.size.1);
+    let mut state = State::new(None, size).await;
+    state.disable_dynamic_resolution();
+    if options.record.is_some() {
+        state.input_recorder = Some(replay::InputRecorder::new(recording.size));
+    }
+    if let Some(directory) = &options.capture {
+        std::fs::create_dir_all(directory).unwrap();
+    }
+
+    let mut input_replay = replay::InputReplay::new(recording);
+    let mut frames = 0;
+    while let Some(frame) = input_replay.next_frame() {
+        state.replay_frame(&frame);
+        frames += 1;
+        // drawing into a texture can't lose a surface
+        state.render().unwrap();
+        if let Some(directory) = &options.capture {
+            let file = directory.join(format!("frame_{:05}.png", frames));
+            if let Err(e) = state.capture_frame(&file) {
+                log::error!("Couldn't capture {}: {}", file.display(), e);
+            }
+        }
+        if state.exit_requested {
+            break;
+        }
+    }
+    log::info!("Replayed {} frames", frames);
+
+    if let (Some(recorder), Some(file)) = (state.input_recorder.take(), options.record.as_ref()) {
+        save_input_recording(recorder, file);
+    }
+}
@@ -1,8 +1,10 @@
 use cgmath::*;
-use winit::event::*;
-use winit::keyboard::KeyCode;
 use std::time::Duration;
 use std::f32::consts::FRAC_PI_2;
+use wgpu::util::DeviceExt;
+use super::uniform::CameraUniform;
+use super::pipeline::DepthMode;
+use super::input::{Action, Axis};
 
 const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
@@ -30,27 +32,188 @@ impl Camera {
         }
     }
 
-    pub fn calculate_matrix(&self) -> Matrix4<f32> {
+    pub fn forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
 
+        Vector3::new(
+            cos_pitch * cos_yaw,
+            sin_pitch,
+            cos_pitch * sin_yaw
+        ).normalize()
+    }
+
+    pub fn yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
+
+    pub fn set_rotation<Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(&mut self, yaw: Y, pitch: P) {
+        self.yaw = yaw.into();
+        self.pitch = Rad(pitch.into().0.clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+    }
+
+    pub fn calculate_matrix(&self) -> Matrix4<f32> {
         Matrix4::look_to_rh(
             self.position,
-            Vector3::new(
-                cos_pitch * cos_yaw,
-                sin_pitch,
-                cos_pitch * sin_yaw
-            ).normalize(),
+            self.forward(),
             Vector3::unit_y()
         )
     }
 }
 
+// a camera entity, the one with the highest priority is the active camera
+// the controllers move, the others are drawn over it in descending priority
+pub struct CameraComponent {
+    pub camera: Camera,
+    pub projection: Projection,
+    // x, y, width and height as fractions of the internal render target
+    pub viewport: [f32; 4],
+    pub priority: i32,
+    uniform: CameraUniform,
+    buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup
+}
+
+impl CameraComponent {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        camera: Camera,
+        projection: Projection,
+        viewport: [f32; 4],
+        priority: i32
+    ) -> Self {
+        let uniform = CameraUniform::new();
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        Self {
+            camera,
+            projection,
+            viewport,
+            priority,
+            uniform,
+            buffer,
+            bind_group
+        }
+    }
+
+    // the viewport in pixels of a target of the given size, kept inside the
+    // target so it can be used as a scissor rect too
+    pub fn viewport_rect(&self, size: (u32, u32)) -> (f32, f32, f32, f32) {
+        let [x, y, width, height] = self.viewport;
+        let (target_width, target_height) = (size.0 as f32, size.1 as f32);
+        let x = (x * target_width).round().clamp(0.0, target_width - 1.0);
+        let y = (y * target_height).round().clamp(0.0, target_height - 1.0);
+        (
+            x,
+            y,
+            (width * target_width).round().clamp(1.0, target_width - x),
+            (height * target_height).round().clamp(1.0, target_height - y)
+        )
+    }
+
+    // the projection aspect follows the viewport's share of the size the
+    // image is shown at, which only differs from the render target size
+    // when the upscale stretches it
+    pub fn resize(&mut self, view_size: (u32, u32), target_size: (u32, u32)) {
+        let (_, _, width, height) = self.viewport_rect(view_size);
+        let target_rect = self.viewport_rect(target_size);
+        self.projection.resize(width as u32, height as u32, target_rect.3);
+        self.uniform.update_viewport(target_rect);
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection.calculate_matrix() * self.camera.calculate_matrix()
+    }
+
+    pub fn update_buffer(&mut self, queue: &wgpu::Queue) {
+        self.uniform.update_view_projection(&self.camera, &self.projection);
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+}
+
+// highest priority wins, the first one on ties
+pub fn active_camera(cameras: &mut [Option<CameraComponent>]) -> Option<&mut CameraComponent> {
+    cameras.iter_mut()
+        .flatten()
+        .rev()
+        .max_by_key(|camera| camera.priority)
+}
+
+// draw order, the active camera first and everything else over it
+pub fn cameras_by_priority(cameras: &[Option<CameraComponent>]) -> Vec<&CameraComponent> {
+    let mut cameras: Vec<&CameraComponent> = cameras.iter().flatten().collect();
+    cameras.sort_by_key(|camera| std::cmp::Reverse(camera.priority));
+    cameras
+}
+
+// cgmath builds projections for opengl's -1..1 depth range, wgpu clips
+// everything below 0
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0
+);
+
+// depth becomes 1 - depth
+#[rustfmt::skip]
+const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 0.0, 1.0, 1.0
+);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectionKind {
+    Perspective,
+    Orthographic
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OrthographicSize {
+    // world units across the viewport height
+    Height(f32),
+    // a world unit covers this many render target pixels whatever the
+    // viewport size, for pixel art
+    PixelsPerUnit(f32)
+}
+
+// keeps both the field of view and the orthographic size so the kind can
+// be switched without losing either
 pub struct Projection {
+    pub kind: ProjectionKind,
     aspect: f32,
+    // viewport height on the render target, what pixels per unit counts
+    target_height: f32,
     fovy: Rad<f32>,
+    size: OrthographicSize,
     znear: f32,
-    zfar: f32
+    // ignored by reverse z perspective, that one never clips the distance
+    zfar: f32,
+    depth_mode: DepthMode
 }
 
 impl Projection {
@@ -62,109 +225,371 @@ impl Projection {
         zfar: f32
     ) -> Self {
         Self {
+            kind: ProjectionKind::Perspective,
             aspect: width as f32 / height as f32,
+            target_height: height as f32,
             fovy: fovy.into(),
+            size: OrthographicSize::Height(10.0),
             znear,
-            zfar
+            zfar,
+            depth_mode: DepthMode::Standard
         }
     }
 
-    pub fn resize(&mut self, width: u32, height: u32) {
+    pub fn depth_mode(&self) -> DepthMode {
+        self.depth_mode
+    }
+
+    pub fn set_depth_mode(&mut self, depth_mode: DepthMode) {
+        self.depth_mode = depth_mode;
+    }
+
+    pub fn fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        self.fovy = fovy.into();
+    }
+
+    // tangents of half the view angle across and up the viewport, the sky
+    // uses these in both kinds so an orthographic view still shows it
+    pub fn sky_extent(&self) -> [f32; 2] {
+        let half_height = (self.fovy.0 * 0.5).tan();
+        [half_height * self.aspect, half_height]
+    }
+
+    pub fn set_orthographic_size(&mut self, size: OrthographicSize) {
+        self.size = size;
+    }
+
+    pub fn toggle_kind(&mut self) -> ProjectionKind {
+        self.kind = match self.kind {
+            ProjectionKind::Perspective => ProjectionKind::Orthographic,
+            ProjectionKind::Orthographic => ProjectionKind::Perspective
+        };
+        self.kind
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32, target_height: f32) {
         self.aspect = width as f32 / height as f32;
+        self.target_height = target_height;
+    }
+
+    // world units visible across the viewport height when orthographic
+    fn orthographic_height(&self) -> f32 {
+        match self.size {
+            OrthographicSize::Height(height) => height,
+            OrthographicSize::PixelsPerUnit(pixels) => self.target_height / pixels
+        }
+    }
+
+    // scales the orthographic view, it follows orbit zoom in perspective
+    // too so switching kinds keeps roughly the same framing
+    pub fn zoom(&mut self, factor: f32) {
+        self.size = match self.size {
+            OrthographicSize::Height(height) => OrthographicSize::Height(height * factor),
+            OrthographicSize::PixelsPerUnit(pixels) => OrthographicSize::PixelsPerUnit(pixels / factor)
+        };
+    }
+
+    // how far away a sphere has to be to fit the view in both directions,
+    // orthographic views use it too so the near plane stays clear of it
+    pub fn fit_distance(&self, radius: f32) -> f32 {
+        let half_fovy = self.fovy.0 * 0.5;
+        let half_fovx = (half_fovy.tan() * self.aspect).atan();
+        radius / half_fovy.min(half_fovx).sin()
+    }
+
+    // sizes the orthographic view around a sphere in both directions
+    pub fn fit_orthographic_size(&mut self, radius: f32) {
+        let height = radius * 2.0 * (1.0 / self.aspect).max(1.0);
+        self.zoom(height / self.orthographic_height());
     }
 
     pub fn calculate_matrix(&self) -> Matrix4<f32> {
-        perspective(
-            self.fovy,
-            self.aspect,
-            self.znear,
-            self.zfar
-        )
+        let projection = match self.kind {
+            ProjectionKind::Perspective if self.depth_mode == DepthMode::Reverse => {
+                return self.infinite_reverse_perspective();
+            }
+            ProjectionKind::Perspective => perspective(
+                self.fovy,
+                self.aspect,
+                self.znear,
+                self.zfar
+            ),
+            ProjectionKind::Orthographic => {
+                let half_height = self.orthographic_height() * 0.5;
+                let half_width = half_height * self.aspect;
+                ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar
+                )
+            }
+        };
+        let projection = OPENGL_TO_WGPU_MATRIX * projection;
+        match self.depth_mode {
+            // orthographic depth is linear so it gains nothing from an
+            // infinite far plane, it just gets flipped
+            DepthMode::Reverse => REVERSE_Z_MATRIX * projection,
+            DepthMode::Standard => projection
+        }
+    }
+
+    // depth is near / distance, 1 at the near plane down to 0 at infinity
+    fn infinite_reverse_perspective(&self) -> Matrix4<f32> {
+        let focal = 1.0 / (self.fovy.0 * 0.5).tan();
+        #[rustfmt::skip]
+        let projection = Matrix4::new(
+            focal / self.aspect, 0.0, 0.0, 0.0,
+            0.0, focal, 0.0, 0.0,
+            0.0, 0.0, 0.0, -1.0,
+            0.0, 0.0, self.znear, 0.0
+        );
+        projection
     }
 }
 
+// input driven camera movement, State holds one behind this so fly and
+// orbit can be switched at runtime
+pub trait CameraController {
+    fn name(&self) -> &str;
+
+    // called when the controller takes over the camera
+    fn attach(&mut self, _camera: &Camera) {}
+
+    // true when the controller used the action
+    fn process_action(&mut self, action: Action, pressed: bool) -> bool;
+    fn process_axis(&mut self, axis: Axis, value: f32);
+
+    fn update_camera(&mut self, camera: &mut Camera, projection: &mut Projection, dt: Duration);
+
+    // puts the camera distance away from center, looking at it
+    fn frame(&mut self, camera: &mut Camera, center: Point3<f32>, distance: f32);
+}
+
+// rates for easing the velocity towards what the keys ask for, per second
+const FLY_ACCELERATION: f32 = 8.0;
+const FLY_DAMPING: f32 = 12.0;
+const FLY_SPRINT_MULTIPLIER: f32 = 3.0;
+const FLY_SLOW_MULTIPLIER: f32 = 0.25;
+// each scroll line changes the base speed by this factor
+const FLY_SCROLL_FACTOR: f32 = 1.2;
+const FLY_MIN_SPEED: f32 = 0.25;
+const FLY_MAX_SPEED: f32 = 100.0;
+
+// every key is tracked on its own so holding A and D cancels out, and
+// letting go of one leaves the other moving
+#[derive(Debug, Default)]
+struct FlyKeys {
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+    sprint: bool,
+    slow: bool
+}
+
+fn key_axis(positive: bool, negative: bool) -> f32 {
+    positive as i32 as f32 - negative as i32 as f32
+}
+
 #[derive(Debug)]
-pub struct CameraController {
-    amount_right: f32,
-    amount_forward: f32,
-    amount_up: f32,
+pub struct FlyController {
+    keys: FlyKeys,
+    velocity: Vector3<f32>,
     rotate_horizontal: f32,
     rotate_vertical: f32,
     speed: f32,
     sensitivity: f32
 }
 
-impl CameraController {
+impl FlyController {
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
-            amount_right: 0.0,
-            amount_forward: 0.0,
-            amount_up: 0.0,
+            keys: FlyKeys::default(),
+            velocity: Vector3::zero(),
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
-            speed: speed,
-            sensitivity: sensitivity
+            speed,
+            sensitivity
         }
     }
+}
 
-    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
-        let amount = if state == ElementState::Pressed { 1.0 } else { 0.0 };
-        match key {
-            KeyCode::KeyW => {
-                self.amount_forward = amount;
-                true
-            }
-            KeyCode::KeyA => {
-                self.amount_right = -amount;
-                true
-            }
-            KeyCode::KeyS => {
-                self.amount_forward = -amount;
-                true
-            }
-            KeyCode::KeyD => {
-                self.amount_right = amount;
-                true
-            }
-            KeyCode::Space => {
-                self.amount_up = amount;
-                true
-            }
-            KeyCode::ShiftLeft => {
-                self.amount_up = -amount;
-                true
-            }
-            _ => false
-        }
+impl CameraController for FlyController {
+    fn name(&self) -> &str {
+        "fly"
     }
 
-    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+    // whatever was moving the camera before doesn't carry over
+    fn attach(&mut self, _camera: &Camera) {
+        self.velocity = Vector3::zero();
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+    fn process_action(&mut self, action: Action, pressed: bool) -> bool {
+        let held = match action {
+            Action::MoveForward => &mut self.keys.forward,
+            Action::MoveBackward => &mut self.keys.backward,
+            Action::MoveLeft => &mut self.keys.left,
+            Action::MoveRight => &mut self.keys.right,
+            Action::MoveUp => &mut self.keys.up,
+            Action::MoveDown => &mut self.keys.down,
+            Action::Sprint => &mut self.keys.sprint,
+            Action::Slow => &mut self.keys.slow,
+            _ => return false
+        };
+        *held = pressed;
+        true
+    }
+
+    // several motion events can arrive between frames, all of them count
+    fn process_axis(&mut self, axis: Axis, value: f32) {
+        match axis {
+            Axis::LookX => self.rotate_horizontal += value,
+            Axis::LookY => self.rotate_vertical += value,
+            Axis::Zoom => {
+                self.speed = (self.speed * FLY_SCROLL_FACTOR.powf(value)).clamp(FLY_MIN_SPEED, FLY_MAX_SPEED);
+            }
+        }
+    }
+
+    fn update_camera(&mut self, camera: &mut Camera, _projection: &mut Projection, dt: Duration) {
         let dt = dt.as_secs_f32();
 
+        // one count of mouse movement always turns the camera the same amount,
+        // however many frames it's spread over
+        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * 0.01;
+        camera.pitch = Rad((camera.pitch.0 - self.rotate_vertical * self.sensitivity * 0.01)
+            .clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
+        let keys = &self.keys;
+        let direction = forward * key_axis(keys.forward, keys.backward)
+            + right * key_axis(keys.right, keys.left)
+            + Vector3::unit_y() * key_axis(keys.up, keys.down);
 
-        camera.position += forward * self.amount_forward * self.speed * dt;
-        camera.position += right * self.amount_right * self.speed * dt;
+        let mut speed = self.speed;
+        if keys.sprint {
+            speed *= FLY_SPRINT_MULTIPLIER;
+        }
+        if keys.slow {
+            speed *= FLY_SLOW_MULTIPLIER;
+        }
+        // diagonals aren't any faster than straight lines
+        let target = if direction.magnitude2() > 0.0 {
+            direction.normalize() * speed
+        } else {
+            Vector3::zero()
+        };
 
-        camera.position.y += self.amount_up * self.speed * dt;
+        // exponential easing comes out the same however dt is split up
+        let rate = if target == Vector3::zero() { FLY_DAMPING } else { FLY_ACCELERATION };
+        self.velocity += (target - self.velocity) * (1.0 - (-rate * dt).exp());
+        camera.position += self.velocity * dt;
+    }
 
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+    // backs off along the current view direction until the bounds fit
+    fn frame(&mut self, camera: &mut Camera, center: Point3<f32>, distance: f32) {
+        self.velocity = Vector3::zero();
+        camera.position = center - camera.forward() * distance;
+    }
+}
 
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+#[derive(Debug)]
+pub struct OrbitController {
+    target: Point3<f32>,
+    distance: f32,
+    rotating: bool,
+    panning: bool,
+    mouse_dx: f32,
+    mouse_dy: f32,
+    scroll: f32,
+    sensitivity: f32
+}
 
-        if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = -Rad(SAFE_FRAC_PI_2);
-        } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = Rad(SAFE_FRAC_PI_2);
+impl OrbitController {
+    pub fn new(sensitivity: f32) -> Self {
+        Self {
+            target: Point3::origin(),
+            distance: 10.0,
+            rotating: false,
+            panning: false,
+            mouse_dx: 0.0,
+            mouse_dy: 0.0,
+            scroll: 0.0,
+            sensitivity
         }
     }
 }
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &str {
+        "orbit"
+    }
+
+    // orbits whatever the camera was looking at, at the same distance
+    fn attach(&mut self, camera: &Camera) {
+        self.target = camera.position + camera.forward() * self.distance;
+    }
+
+    fn process_action(&mut self, action: Action, pressed: bool) -> bool {
+        match action {
+            Action::Rotate => self.rotating = pressed,
+            Action::Pan => self.panning = pressed,
+            _ => return false
+        }
+        true
+    }
+
+    fn process_axis(&mut self, axis: Axis, value: f32) {
+        match axis {
+            Axis::LookX => self.mouse_dx += value,
+            Axis::LookY => self.mouse_dy += value,
+            Axis::Zoom => self.scroll += value
+        }
+    }
+
+    fn update_camera(&mut self, camera: &mut Camera, projection: &mut Projection, _dt: Duration) {
+        // mouse deltas are already per frame, scaling them by dt again
+        // would make the orbit speed depend on the frame rate
+        if self.rotating {
+            camera.yaw += Rad(self.mouse_dx) * self.sensitivity * 0.01;
+            camera.pitch = Rad((camera.pitch.0 - self.mouse_dy * self.sensitivity * 0.01)
+                .clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+        }
+        let forward = camera.forward();
+        if self.panning {
+            // moves the target with the cursor, faster the further out
+            let right = forward.cross(Vector3::unit_y()).normalize();
+            let up = right.cross(forward);
+            let scale = self.distance * 0.002;
+            self.target += (-right * self.mouse_dx + up * self.mouse_dy) * scale;
+        }
+        let zoom = 0.9f32.powf(self.scroll);
+        self.distance = (self.distance * zoom).max(0.01);
+        projection.zoom(zoom);
+
+        self.mouse_dx = 0.0;
+        self.mouse_dy = 0.0;
+        self.scroll = 0.0;
+
+        camera.position = self.target - forward * self.distance;
+    }
+
+    fn frame(&mut self, camera: &mut Camera, center: Point3<f32>, distance: f32) {
+        self.target = center;
+        self.distance = distance;
+        camera.position = center - camera.forward() * distance;
+    }
+}
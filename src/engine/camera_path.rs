@@ -0,0 +1,274 @@
+use cgmath::*;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use super::camera::{Camera, Projection};
+
+// how often the recorder samples the camera, the spline smooths out the rest
+const RECORD_INTERVAL: f32 = 0.25;
+
+// angles are in degrees like the scene camera block
+#[derive(Debug, Clone, Copy)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov: f32
+}
+
+impl CameraKeyframe {
+    fn from_camera(time: f32, camera: &Camera, projection: &Projection) -> Self {
+        Self {
+            time,
+            position: camera.position,
+            yaw: Deg::from(camera.yaw()).0,
+            pitch: Deg::from(camera.pitch()).0,
+            fov: Deg::from(projection.fovy()).0
+        }
+    }
+
+    fn apply(&self, camera: &mut Camera, projection: &mut Projection) {
+        camera.position = self.position;
+        camera.set_rotation(Deg(self.yaw), Deg(self.pitch));
+        projection.set_fovy(Deg(self.fov));
+    }
+}
+
+// keyframes sorted by time, yaw isn't wrapped so a path can turn past 360
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    pub name: String,
+    keyframes: Vec<CameraKeyframe>
+}
+
+impl CameraPath {
+    // panics on malformed paths like the rest of the scene parsing
+    pub fn from_json(path: &Value) -> Self {
+        let mut keyframes: Vec<CameraKeyframe> = path["points"].as_array().unwrap()
+            .iter()
+            .map(|point| {
+                let position = point["position"].as_array().unwrap();
+                CameraKeyframe {
+                    time: point["time"].as_f64().unwrap() as f32,
+                    position: Point3::new(
+                        position[0].as_f64().unwrap() as f32,
+                        position[1].as_f64().unwrap() as f32,
+                        position[2].as_f64().unwrap() as f32
+                    ),
+                    yaw: point["yaw"].as_f64().unwrap_or(-90.0) as f32,
+                    pitch: point["pitch"].as_f64().unwrap_or(0.0) as f32,
+                    fov: point["fov"].as_f64().unwrap_or(45.0) as f32
+                }
+            })
+            .collect();
+        assert!(!keyframes.is_empty(), "Camera path without points");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        // the spline divides by the time between keyframes
+        assert!(
+            keyframes.windows(2).all(|pair| pair[0].time < pair[1].time),
+            "Camera path with two points at the same time"
+        );
+
+        Self {
+            name: path["name"].as_str().unwrap_or("unnamed").to_string(),
+            keyframes
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "points": self.keyframes.iter().map(|keyframe| json!({
+                "time": rounded(keyframe.time),
+                "position": [rounded(keyframe.position.x), rounded(keyframe.position.y), rounded(keyframe.position.z)],
+                "yaw": rounded(keyframe.yaw),
+                "pitch": rounded(keyframe.pitch),
+                "fov": rounded(keyframe.fov)
+            })).collect::<Vec<_>>()
+        })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().unwrap().time
+    }
+
+    // catmull-rom through the keyframes, the tangents are divided by the
+    // time between neighbours so uneven timing doesn't overshoot
+    pub fn sample(&self, time: f32) -> CameraKeyframe {
+        let points = &self.keyframes;
+        let last = points.len() - 1;
+        let time = time.clamp(points[0].time, points[last].time);
+        if last == 0 {
+            return points[0];
+        }
+
+        let i = points.windows(2).position(|pair| time <= pair[1].time).unwrap_or(last - 1);
+        let (a, b) = (&points[i], &points[i + 1]);
+        let duration = (b.time - a.time).max(f32::EPSILON);
+        let t = (time - a.time) / duration;
+
+        let tangent = |j: usize, value: fn(&CameraKeyframe) -> f32| {
+            let (previous, next) = (&points[j.saturating_sub(1)], &points[(j + 1).min(last)]);
+            (value(next) - value(previous)) / (next.time - previous.time).max(f32::EPSILON)
+        };
+        let interpolate = |value: fn(&CameraKeyframe) -> f32| {
+            hermite(value(a), tangent(i, value) * duration, value(b), tangent(i + 1, value) * duration, t)
+        };
+
+        CameraKeyframe {
+            time,
+            position: Point3::new(
+                interpolate(|k| k.position.x),
+                interpolate(|k| k.position.y),
+                interpolate(|k| k.position.z)
+            ),
+            yaw: interpolate(|k| k.yaw),
+            pitch: interpolate(|k| k.pitch),
+            fov: interpolate(|k| k.fov)
+        }
+    }
+}
+
+// f32s written as json pick up digits they never had, 0.256 as 0.25600001
+fn rounded(value: f32) -> f64 {
+    (value as f64 * 1000.0).round() / 1000.0
+}
+
+fn hermite(p0: f32, m0: f32, p1: f32, m1: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (2.0 * t3 - 3.0 * t2 + 1.0) * p0
+        + (t3 - 2.0 * t2 + t) * m0
+        + (-2.0 * t3 + 3.0 * t2) * p1
+        + (t3 - t2) * m1
+}
+
+// drives the active camera instead of the controller until the path ends
+pub struct CameraPathPlayer {
+    path: CameraPath,
+    time: f32
+}
+
+impl CameraPathPlayer {
+    pub fn new(path: CameraPath) -> Self {
+        Self { time: path.keyframes[0].time, path }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.path.name
+    }
+
+    // false once the end of the path has been shown
+    pub fn update(&mut self, camera: &mut Camera, projection: &mut Projection, dt: Duration) -> bool {
+        self.path.sample(self.time).apply(camera, projection);
+        let playing = self.time < self.path.duration();
+        self.time += dt.as_secs_f32();
+        playing
+    }
+}
+
+pub struct CameraPathRecorder {
+    keyframes: Vec<CameraKeyframe>,
+    time: f32,
+    next_sample: f32
+}
+
+impl CameraPathRecorder {
+    pub fn new() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            time: 0.0,
+            next_sample: 0.0
+        }
+    }
+
+    pub fn update(&mut self, camera: &Camera, projection: &Projection, dt: Duration) {
+        if self.time >= self.next_sample {
+            self.keyframes.push(CameraKeyframe::from_camera(self.time, camera, projection));
+            self.next_sample += RECORD_INTERVAL;
+        }
+        self.time += dt.as_secs_f32();
+    }
+
+    // the last position is kept even when it falls between samples, unless
+    // it's so close to the last sample that both get saved as the same time
+    pub fn finish(mut self, name: &str, camera: &Camera, projection: &Projection) -> CameraPath {
+        if self.keyframes.last().is_none_or(|keyframe| rounded(keyframe.time) < rounded(self.time)) {
+            self.keyframes.push(CameraKeyframe::from_camera(self.time, camera, projection));
+        }
+        CameraPath {
+            name: name.to_string(),
+            keyframes: self.keyframes
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x moves at a constant 2 units a second, yaw doesn't
+    fn path() -> CameraPath {
+        CameraPath::from_json(&json!({
+            "name": "test",
+            "points": [
+                { "time": 3.0, "position": [6.0, 0.0, 1.0], "yaw": 90.0 },
+                { "time": 0.0, "position": [0.0, 0.0, 1.0], "yaw": 0.0 },
+                { "time": 1.0, "position": [2.0, 0.0, 1.0], "yaw": 45.0 }
+            ]
+        }))
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn sample_hits_the_keyframes() {
+        let path = path();
+        assert_close(path.duration(), 3.0);
+        for (time, x, yaw) in [(0.0, 0.0, 0.0), (1.0, 2.0, 45.0), (3.0, 6.0, 90.0)] {
+            let keyframe = path.sample(time);
+            assert_close(keyframe.position.x, x);
+            assert_close(keyframe.position.z, 1.0);
+            assert_close(keyframe.yaw, yaw);
+            assert_close(keyframe.fov, 45.0);
+        }
+    }
+
+    #[test]
+    fn sample_clamps_past_the_endpoints() {
+        let path = path();
+        assert_close(path.sample(-1.0).time, 0.0);
+        assert_close(path.sample(-1.0).position.x, 0.0);
+        assert_close(path.sample(10.0).time, 3.0);
+        assert_close(path.sample(10.0).position.x, 6.0);
+    }
+
+    #[test]
+    fn sample_between_keyframes_follows_uneven_timing() {
+        let path = path();
+        // constant speed stays constant however far apart the keyframes are
+        for time in [0.25, 0.5, 1.5, 2.0, 2.75] {
+            assert_close(path.sample(time).position.x, time * 2.0);
+        }
+        // and turning doesn't overshoot either end
+        for time in [0.1, 0.5, 0.9, 1.5, 2.5, 2.9] {
+            let yaw = path.sample(time).yaw;
+            assert!((0.0..=90.0).contains(&yaw), "yaw {} at {}", yaw, time);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "same time")]
+    fn duplicate_times_are_rejected() {
+        CameraPath::from_json(&json!({
+            "points": [
+                { "time": 0.0, "position": [0.0, 0.0, 0.0] },
+                { "time": 1.0, "position": [1.0, 0.0, 0.0] },
+                { "time": 1.0, "position": [2.0, 0.0, 0.0] }
+            ]
+        }));
+    }
+}
@@ -0,0 +1,156 @@
+use cgmath::*;
+
+use super::{model, ecs::transform::Transform};
+
+// planes pointing inwards as (normal, distance), built from a view
+// projection so it works for any projection and either depth mode
+pub struct Frustum {
+    planes: Vec<Vector4<f32>>
+}
+
+impl Frustum {
+    pub fn from_matrix(view_projection: &Matrix4<f32>) -> Self {
+        let m = view_projection;
+        let (x, y, z, w) = (m.row(0), m.row(1), m.row(2), m.row(3));
+        // wgpu clips depth to 0..w, with reverse z and an infinite far
+        // plane one of these has no normal and is left out
+        let planes = [w + x, w - x, w + y, w - y, z, w - z]
+            .into_iter()
+            .filter_map(|plane| {
+                let length = plane.truncate().magnitude();
+                (length > f32::EPSILON).then(|| plane / length)
+            })
+            .collect();
+        Self { planes }
+    }
+
+    fn distance(plane: &Vector4<f32>, point: Point3<f32>) -> f32 {
+        plane.truncate().dot(point.to_vec()) + plane.w
+    }
+
+    pub fn intersects_sphere(&self, sphere: &model::BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| Self::distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    // only the corner furthest along each plane's normal needs checking
+    pub fn intersects_aabb(&self, bounds: &model::Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let corner = Point3::new(
+                if plane.x >= 0.0 { bounds.max.x } else { bounds.min.x },
+                if plane.y >= 0.0 { bounds.max.y } else { bounds.min.y },
+                if plane.z >= 0.0 { bounds.max.z } else { bounds.min.z }
+            );
+            Self::distance(plane, corner) >= 0.0
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CullingStats {
+    pub meshes: usize,
+    pub culled: usize
+}
+
+// the meshes of every entity that can show up in the frustum, indexed by
+// entity like the component vecs. the sphere is cheaper so it goes first,
+// the box catches long thin meshes the sphere is much too big for
+pub fn visible_meshes<'a>(
+    frustum: &Frustum,
+    models: &'a [Option<model::Model>],
+    transforms: &[Option<Transform>],
+    stats: &mut CullingStats
+) -> Vec<Vec<&'a model::Mesh>> {
+    models.iter()
+        .zip(transforms.iter())
+        .map(|(model, transform)| {
+            let (Some(model), Some(transform)) = (model, transform) else {
+                return Vec::new();
+            };
+            let matrix = transform.mat4();
+            let visible: Vec<&model::Mesh> = model.meshes.iter()
+                .filter(|mesh| {
+                    frustum.intersects_sphere(&mesh.sphere.transform(&matrix))
+                        && frustum.intersects_aabb(&mesh.bounds.transform(&matrix))
+                })
+                .collect();
+            stats.meshes += model.meshes.len();
+            stats.culled += model.meshes.len() - visible.len();
+            visible
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{camera::Projection, pipeline::DepthMode};
+
+    // a camera at the origin looking down -z, 90 degrees wide and high
+    // so the side planes sit at |x| = |y| = -z
+    fn frustum(depth_mode: DepthMode) -> Frustum {
+        let mut projection = Projection::new(100, 100, Deg(90.0), 0.1, 100.0);
+        projection.set_depth_mode(depth_mode);
+        Frustum::from_matrix(&projection.calculate_matrix())
+    }
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> model::BoundingSphere {
+        model::BoundingSphere { center: Point3::new(x, y, z), radius }
+    }
+
+    fn aabb(min: [f32; 3], max: [f32; 3]) -> model::Aabb {
+        model::Aabb { min: Point3::from(min), max: Point3::from(max) }
+    }
+
+    #[test]
+    fn planes_from_both_depth_modes() {
+        assert_eq!(frustum(DepthMode::Standard).planes.len(), 6);
+        // the infinite reverse z projection has no far plane
+        assert_eq!(frustum(DepthMode::Reverse).planes.len(), 5);
+    }
+
+    #[test]
+    fn spheres() {
+        for depth_mode in [DepthMode::Standard, DepthMode::Reverse] {
+            let frustum = frustum(depth_mode);
+            // inside
+            assert!(frustum.intersects_sphere(&sphere(0.0, 0.0, -10.0, 1.0)));
+            assert!(frustum.intersects_sphere(&sphere(5.0, -5.0, -10.0, 0.5)));
+            // behind, beside, above and in front of the near plane
+            assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, 10.0, 1.0)));
+            assert!(!frustum.intersects_sphere(&sphere(20.0, 0.0, -10.0, 1.0)));
+            assert!(!frustum.intersects_sphere(&sphere(0.0, 20.0, -10.0, 1.0)));
+            assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, -0.05, 0.01)));
+            // straddling a side plane and the near plane
+            assert!(frustum.intersects_sphere(&sphere(10.5, 0.0, -10.0, 1.0)));
+            assert!(frustum.intersects_sphere(&sphere(0.0, 0.0, 0.0, 0.5)));
+        }
+
+        let standard = frustum(DepthMode::Standard);
+        let reverse = frustum(DepthMode::Reverse);
+        assert!(standard.intersects_sphere(&sphere(0.0, 0.0, -100.0, 1.0)));
+        assert!(!standard.intersects_sphere(&sphere(0.0, 0.0, -200.0, 1.0)));
+        assert!(reverse.intersects_sphere(&sphere(0.0, 0.0, -200.0, 1.0)));
+    }
+
+    #[test]
+    fn boxes() {
+        for depth_mode in [DepthMode::Standard, DepthMode::Reverse] {
+            let frustum = frustum(depth_mode);
+            // inside
+            assert!(frustum.intersects_aabb(&aabb([-1.0, -1.0, -11.0], [1.0, 1.0, -9.0])));
+            // beside, below and behind
+            assert!(!frustum.intersects_aabb(&aabb([15.0, -1.0, -11.0], [17.0, 1.0, -9.0])));
+            assert!(!frustum.intersects_aabb(&aabb([-1.0, -17.0, -11.0], [1.0, -15.0, -9.0])));
+            assert!(!frustum.intersects_aabb(&aabb([-1.0, -1.0, 1.0], [1.0, 1.0, 3.0])));
+            // straddling a side plane, and a long box through the whole view
+            assert!(frustum.intersects_aabb(&aabb([9.0, -1.0, -11.0], [12.0, 1.0, -9.0])));
+            assert!(frustum.intersects_aabb(&aabb([-50.0, -0.1, -5.1], [50.0, 0.1, -4.9])));
+        }
+
+        let standard = frustum(DepthMode::Standard);
+        let reverse = frustum(DepthMode::Reverse);
+        assert!(standard.intersects_aabb(&aabb([-1.0, -1.0, -101.0], [1.0, 1.0, -99.0])));
+        assert!(!standard.intersects_aabb(&aabb([-1.0, -1.0, -201.0], [1.0, 1.0, -199.0])));
+        assert!(reverse.intersects_aabb(&aabb([-1.0, -1.0, -201.0], [1.0, 1.0, -199.0])));
+    }
+}
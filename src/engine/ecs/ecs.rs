@@ -73,7 +73,7 @@ impl World {
 
     pub fn borrow_component_vec<ComponentType: 'static>(
         &self
-    ) -> Option<RefMut<Vec<Option<ComponentType>>>> {
+    ) -> Option<RefMut<'_, Vec<Option<ComponentType>>>> {
         for component_vec in self.component_vecs.iter() {
             if let Some(component_vec) = component_vec
                 .as_any()
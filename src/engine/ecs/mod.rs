@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod ecs;
 pub mod transform;
 pub mod scene;
@@ -1,40 +1,81 @@
 use super::{
     ecs,
     transform,
-    super::resources
+    super::{resources, uniform, camera, camera_path, textures::pbr}
 };
 use serde_json::Value;
 
+pub struct Scene {
+    pub world: ecs::World,
+    // per effect blocks laid over the ones in the settings file
+    pub post_processing: Value,
+    pub fog: uniform::FogUniform,
+    pub camera_paths: Vec<camera_path::CameraPath>
+}
+
 pub async fn parse_scene(
     file: &str,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     texture_layout: &wgpu::BindGroupLayout,
-    cubemap_layout: &wgpu::BindGroupLayout
-) -> ecs::World {
+    cubemap_layout: &wgpu::BindGroupLayout,
+    pbr_layout: &wgpu::BindGroupLayout,
+    camera_layout: &wgpu::BindGroupLayout
+) -> Scene {
     let mut world = ecs::World::new();
 
     let scene: Value = serde_json::from_str(file).unwrap();
     let entities = scene["entities"].as_array().unwrap();
+    let mut has_camera = false;
 
     for entity in entities {
         let world_entity = world.new_entity();
 
         if let Some(model_path) = entity["model_path"].as_str() {
-            let entity_model = if model_path.ends_with(".obj") {
-                resources::load_model(model_path, device).await.unwrap()
+            let (entity_model, gltf_materials) = if model_path.ends_with(".obj") {
+                (resources::load_model(model_path, device).await.unwrap(), None)
             } else if model_path.ends_with(".gltf") || model_path.ends_with(".glb") {
-                resources::load_gltf(model_path, device).await.unwrap()
+                let (model, materials) = resources::load_gltf(model_path, device, queue, pbr_layout)
+                    .await
+                    .unwrap();
+                (model, Some(materials))
             } else {
                 panic!("Unsupported model format in scene: {}", model_path);
             };
             world.add_component_to_entity(world_entity, entity_model);
 
-            let texture_path = entity["texture_path"].as_str().unwrap_or("debug.png");
-            let entity_texture = resources::load_material(device, queue, texture_layout, texture_path)
-                .await
-                .unwrap();
-            world.add_component_to_entity(world_entity, entity_texture);
+            // glTF models default to their imported pbr materials, anything
+            // else only uses pbr when asked to or when given a pbr block
+            let default_material = if gltf_materials.is_some() || entity["pbr"].is_object() {
+                "pbr"
+            } else {
+                "simple"
+            };
+            match entity["material"].as_str().unwrap_or(default_material) {
+                "simple" => {
+                    let texture_path = entity["texture_path"].as_str().unwrap_or("debug.png");
+                    let specular_path = entity["specular_path"].as_str();
+                    let specular_color = parse_f32_array(&entity["specular_color"])
+                        .map(cgmath::Vector3::from)
+                        .unwrap_or(cgmath::vec3(0.1, 0.1, 0.1));
+                    let shininess = entity["shininess"].as_f64().unwrap_or(32.0) as f32;
+                    let entity_texture = resources::load_material(
+                        device, queue, texture_layout, texture_path, specular_path, specular_color, shininess
+                    ).await.unwrap();
+                    world.add_component_to_entity(world_entity, entity_texture);
+                }
+                "pbr" => {
+                    // an explicit pbr block replaces any imported glTF materials
+                    let entity_materials = match gltf_materials {
+                        Some(materials) if !entity["pbr"].is_object() => materials,
+                        _ => pbr::PbrMaterialComponent {
+                            materials: vec![parse_pbr_material(&entity["pbr"], device, queue, pbr_layout).await]
+                        }
+                    };
+                    world.add_component_to_entity(world_entity, entity_materials);
+                }
+                material => panic!("Unsupported material type in scene: {}", material)
+            }
         }
 
         if let Some(transform_obj) = entity["transform"].as_object() {
@@ -64,8 +105,148 @@ pub async fn parse_scene(
                 .await
                 .unwrap();
             world.add_component_to_entity(world_entity, skybox);
+        } else if let Some(skybox_file) = entity["skybox"].as_str() {
+            // a single file is an equirectangular hdr panorama
+            let skybox = resources::load_hdr_cubemap(skybox_file, device, queue, cubemap_layout)
+                .await
+                .unwrap();
+            world.add_component_to_entity(world_entity, skybox);
         }
+
+        if entity["camera"].is_object() {
+            world.add_component_to_entity(world_entity, parse_camera(&entity["camera"], device, camera_layout));
+            has_camera = true;
+        }
+    }
+
+    // scenes without a camera get the one that used to be hard coded
+    if !has_camera {
+        let world_entity = world.new_entity();
+        world.add_component_to_entity(world_entity, parse_camera(&Value::Null, device, camera_layout));
+    }
+
+    // paths are either written out in the scene or a file name, which is
+    // what recording produces
+    let mut camera_paths = Vec::new();
+    for path in scene["camera_paths"].as_array().into_iter().flatten() {
+        camera_paths.push(match path.as_str() {
+            Some(file_name) => resources::load_camera_path(file_name).await.unwrap(),
+            None => camera_path::CameraPath::from_json(path)
+        });
     }
 
-    world
+    Scene {
+        world,
+        post_processing: scene["post_processing"].clone(),
+        fog: parse_fog(&scene["environment"]["fog"]),
+        camera_paths
+    }
+}
+
+fn parse_f32_array<const N: usize>(value: &Value) -> Option<[f32; N]> {
+    let array = value.as_array()?;
+    let mut result = [0.0; N];
+    for (i, element) in result.iter_mut().enumerate() {
+        *element = array.get(i)?.as_f64()? as f32;
+    }
+    Some(result)
+}
+
+// everything is optional, the defaults look at the origin from above
+// the positive z axis, the aspect is set once the viewport size is known
+fn parse_camera(
+    camera: &Value,
+    device: &wgpu::Device,
+    camera_layout: &wgpu::BindGroupLayout
+) -> camera::CameraComponent {
+    let position = parse_f32_array(&camera["position"]).unwrap_or([0.0, 5.0, 10.0]);
+    let yaw = camera["yaw"].as_f64().unwrap_or(-90.0) as f32;
+    let pitch = camera["pitch"].as_f64().unwrap_or(-20.0) as f32;
+
+    let settings = &camera["projection"];
+    let near = settings["near"].as_f64().unwrap_or(0.1) as f32;
+    let far = settings["far"].as_f64().unwrap_or(100.0) as f32;
+    let fov = settings["fov"].as_f64().unwrap_or(45.0) as f32;
+    let mut projection = camera::Projection::new(1, 1, cgmath::Deg(fov), near, far);
+    // orthographic size is either a height in world units or pixels per unit,
+    // perspective cameras keep it for when they're switched over
+    if let Some(size) = settings["size"].as_f64() {
+        projection.set_orthographic_size(camera::OrthographicSize::Height(size as f32));
+    } else if let Some(pixels) = settings["pixels_per_unit"].as_f64() {
+        projection.set_orthographic_size(camera::OrthographicSize::PixelsPerUnit(pixels as f32));
+    }
+    projection.kind = match settings["type"].as_str().unwrap_or("perspective") {
+        "perspective" => camera::ProjectionKind::Perspective,
+        "orthographic" => camera::ProjectionKind::Orthographic,
+        kind => panic!("Unsupported camera projection in scene: {}", kind)
+    };
+
+    camera::CameraComponent::new(
+        device,
+        camera_layout,
+        camera::Camera::new(position, cgmath::Deg(yaw), cgmath::Deg(pitch)),
+        projection,
+        parse_f32_array(&camera["viewport"]).unwrap_or([0.0, 0.0, 1.0, 1.0]),
+        camera["priority"].as_i64().unwrap_or(0) as i32
+    )
+}
+
+// a missing fog block or mode leaves the fog off
+fn parse_fog(fog: &Value) -> uniform::FogUniform {
+    let defaults = uniform::FogUniform::default();
+    let mode = match fog["mode"].as_str().unwrap_or("none") {
+        "none" => 0,
+        "linear" => 1,
+        "exp" => 2,
+        "exp2" => 3,
+        mode => panic!("Unsupported fog mode in scene: {}", mode)
+    };
+    let height = &fog["height"];
+    uniform::FogUniform {
+        // the color is either rgb or "skybox"
+        color: parse_f32_array(&fog["color"]).unwrap_or(defaults.color),
+        mode,
+        density: fog["density"].as_f64().map_or(defaults.density, |f| f as f32),
+        start: fog["start"].as_f64().map_or(defaults.start, |f| f as f32),
+        end: fog["end"].as_f64().map_or(defaults.end, |f| f as f32),
+        from_skybox: (fog["color"].as_str() == Some("skybox")) as u32,
+        height_density: height["density"].as_f64().map_or(defaults.height_density, |f| f as f32),
+        height_falloff: height["falloff"].as_f64().map_or(defaults.height_falloff, |f| f as f32),
+        height_base: height["base"].as_f64().map_or(defaults.height_base, |f| f as f32),
+        sky_distance: fog["sky_distance"].as_f64().map_or(defaults.sky_distance, |f| f as f32)
+    }
+}
+
+async fn parse_pbr_material(
+    pbr: &Value,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pbr_layout: &wgpu::BindGroupLayout
+) -> pbr::PbrMaterial {
+    let defaults = uniform::PbrMaterialUniform::default();
+    let factors = uniform::PbrMaterialUniform {
+        base_color_factor: parse_f32_array(&pbr["base_color_factor"])
+            .unwrap_or(defaults.base_color_factor),
+        emissive_factor: parse_f32_array(&pbr["emissive_factor"])
+            .unwrap_or(defaults.emissive_factor),
+        metallic_factor: pbr["metallic_factor"].as_f64()
+            .map_or(defaults.metallic_factor, |f| f as f32),
+        roughness_factor: pbr["roughness_factor"].as_f64()
+            .map_or(defaults.roughness_factor, |f| f as f32),
+        occlusion_strength: pbr["occlusion_strength"].as_f64()
+            .map_or(defaults.occlusion_strength, |f| f as f32),
+        normal_scale: pbr["normal_scale"].as_f64()
+            .map_or(1.0, |f| f as f32),
+        ..defaults
+    };
+    let file_names = [
+        pbr["base_color_path"].as_str(),
+        pbr["metallic_roughness_path"].as_str(),
+        pbr["normal_path"].as_str(),
+        pbr["occlusion_path"].as_str(),
+        pbr["emissive_path"].as_str()
+    ];
+    resources::load_pbr_material(device, queue, pbr_layout, file_names, factors)
+        .await
+        .unwrap()
 }
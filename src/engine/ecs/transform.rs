@@ -1,5 +1,6 @@
-use cgmath::{Deg, SquareMatrix};
+use cgmath::{Deg, SquareMatrix, VectorSpace};
 
+#[derive(Clone)]
 pub struct Transform {
     pub translation: cgmath::Vector3<f32>,
     pub scale: f32,
@@ -24,6 +25,16 @@ impl Transform {
 
         cgmath::Matrix4::identity() * translation * rotation * scale
     }
+
+    // euler angles are blended as they are, fine for the small steps
+    // between two simulation ticks
+    pub fn lerp(&self, other: &Transform, amount: f32) -> Transform {
+        Transform {
+            translation: self.translation.lerp(other.translation, amount),
+            scale: self.scale + (other.scale - self.scale) * amount,
+            rotation: self.rotation.lerp(other.rotation, amount)
+        }
+    }
 }
 
 #[repr(C)]
@@ -0,0 +1,476 @@
+use serde_json::{json, Value};
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use super::resources;
+
+// everything the engine responds to, bound to keys and buttons by name
+// in res/input.json
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Sprint,
+    Slow,
+    // held to drag the orbit camera around or across
+    Rotate,
+    Pan,
+    SwitchController,
+    SelectNext,
+    FrameSelected,
+    ViewFront,
+    ViewSide,
+    ViewTop,
+    ToggleProjection,
+    PlayPath,
+    RecordPath,
+    NextToneMapping,
+    ExposureDown,
+    ExposureUp,
+    ToggleSsao,
+    NextUpscaleFilter,
+    // post processing effects in chain order
+    ToggleEffect(usize),
+    Rebind,
+    Exit
+}
+
+const ACTION_NAMES: [(Action, &str); 34] = [
+    (Action::MoveForward, "move_forward"),
+    (Action::MoveBackward, "move_backward"),
+    (Action::MoveLeft, "move_left"),
+    (Action::MoveRight, "move_right"),
+    (Action::MoveUp, "move_up"),
+    (Action::MoveDown, "move_down"),
+    (Action::Sprint, "sprint"),
+    (Action::Slow, "slow"),
+    (Action::Rotate, "rotate"),
+    (Action::Pan, "pan"),
+    (Action::SwitchController, "switch_controller"),
+    (Action::SelectNext, "select_next"),
+    (Action::FrameSelected, "frame_selected"),
+    (Action::ViewFront, "view_front"),
+    (Action::ViewSide, "view_side"),
+    (Action::ViewTop, "view_top"),
+    (Action::ToggleProjection, "toggle_projection"),
+    (Action::PlayPath, "play_path"),
+    (Action::RecordPath, "record_path"),
+    (Action::NextToneMapping, "next_tone_mapping"),
+    (Action::ExposureDown, "exposure_down"),
+    (Action::ExposureUp, "exposure_up"),
+    (Action::ToggleSsao, "toggle_ssao"),
+    (Action::NextUpscaleFilter, "next_upscale_filter"),
+    (Action::ToggleEffect(0), "toggle_effect_1"),
+    (Action::ToggleEffect(1), "toggle_effect_2"),
+    (Action::ToggleEffect(2), "toggle_effect_3"),
+    (Action::ToggleEffect(3), "toggle_effect_4"),
+    (Action::ToggleEffect(4), "toggle_effect_5"),
+    (Action::ToggleEffect(5), "toggle_effect_6"),
+    (Action::ToggleEffect(6), "toggle_effect_7"),
+    (Action::ToggleEffect(7), "toggle_effect_8"),
+    (Action::Rebind, "rebind"),
+    (Action::Exit, "exit")
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTION_NAMES.iter().find(|(_, n)| *n == name).map(|(action, _)| *action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ACTION_NAMES.iter().find(|(action, _)| action == self).map(|(_, name)| *name).unwrap()
+    }
+}
+
+// continuous values, mouse movement in counts and the wheel in lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    LookX,
+    LookY,
+    Zoom
+}
+
+impl Axis {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "look_x" => Some(Axis::LookX),
+            "look_y" => Some(Axis::LookY),
+            "zoom" => Some(Axis::Zoom),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Axis::LookX => "look_x",
+            Axis::LookY => "look_y",
+            Axis::Zoom => "zoom"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton)
+}
+
+// every key that can be named in the config, by its winit name
+const KEYS: [KeyCode; 86] = [
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF,
+    KeyCode::KeyG, KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL,
+    KeyCode::KeyM, KeyCode::KeyN, KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR,
+    KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU, KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX,
+    KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+    KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::Numpad4,
+    KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9,
+    KeyCode::ArrowUp, KeyCode::ArrowDown, KeyCode::ArrowLeft, KeyCode::ArrowRight,
+    KeyCode::ShiftLeft, KeyCode::ShiftRight, KeyCode::ControlLeft, KeyCode::ControlRight,
+    KeyCode::AltLeft, KeyCode::AltRight,
+    KeyCode::Space, KeyCode::Tab, KeyCode::Enter, KeyCode::Escape, KeyCode::Backspace,
+    KeyCode::Minus, KeyCode::Equal, KeyCode::BracketLeft, KeyCode::BracketRight,
+    KeyCode::Semicolon, KeyCode::Quote, KeyCode::Comma, KeyCode::Period, KeyCode::Slash,
+    KeyCode::Backslash, KeyCode::Backquote, KeyCode::Home, KeyCode::End
+];
+
+const MOUSE_BUTTONS: [(MouseButton, &str); 5] = [
+    (MouseButton::Left, "MouseLeft"),
+    (MouseButton::Right, "MouseRight"),
+    (MouseButton::Middle, "MouseMiddle"),
+    (MouseButton::Back, "MouseBack"),
+    (MouseButton::Forward, "MouseForward")
+];
+
+impl Binding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some((button, _)) = MOUSE_BUTTONS.iter().find(|(_, n)| *n == name) {
+            return Some(Binding::Mouse(*button));
+        }
+        KEYS.iter().find(|key| format!("{:?}", key) == name).map(|key| Binding::Key(*key))
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => MOUSE_BUTTONS.iter()
+                .find(|(b, _)| b == button)
+                .map_or_else(|| format!("{:?}", button), |(_, name)| name.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AxisSource {
+    MouseX,
+    MouseY,
+    Wheel
+}
+
+// a leading - in the name flips the axis, "-mouse_y" inverts looking
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisBinding {
+    source: AxisSource,
+    scale: f32
+}
+
+impl AxisBinding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (scale, source) = match name.strip_prefix('-') {
+            Some(source) => (-1.0, source),
+            None => (1.0, name)
+        };
+        let source = match source {
+            "mouse_x" => AxisSource::MouseX,
+            "mouse_y" => AxisSource::MouseY,
+            "wheel" => AxisSource::Wheel,
+            _ => return None
+        };
+        Some(Self { source, scale })
+    }
+
+    pub fn name(&self) -> String {
+        let source = match self.source {
+            AxisSource::MouseX => "mouse_x",
+            AxisSource::MouseY => "mouse_y",
+            AxisSource::Wheel => "wheel"
+        };
+        if self.scale < 0.0 { format!("-{}", source) } else { source.to_string() }
+    }
+}
+
+// any number of bindings per action, and one binding can drive several
+pub struct InputMap {
+    actions: Vec<(Action, Binding)>,
+    axes: Vec<(Axis, AxisBinding)>
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let key = |action, key| (action, Binding::Key(key));
+        let mut actions = vec![
+            key(Action::MoveForward, KeyCode::KeyW),
+            key(Action::MoveBackward, KeyCode::KeyS),
+            key(Action::MoveLeft, KeyCode::KeyA),
+            key(Action::MoveRight, KeyCode::KeyD),
+            key(Action::MoveUp, KeyCode::Space),
+            key(Action::MoveDown, KeyCode::ShiftLeft),
+            key(Action::Sprint, KeyCode::ControlLeft),
+            key(Action::Slow, KeyCode::AltLeft),
+            (Action::Rotate, Binding::Mouse(MouseButton::Left)),
+            (Action::Pan, Binding::Mouse(MouseButton::Middle)),
+            key(Action::SwitchController, KeyCode::KeyC),
+            key(Action::SelectNext, KeyCode::Tab),
+            key(Action::FrameSelected, KeyCode::KeyF),
+            key(Action::ViewFront, KeyCode::Numpad1),
+            key(Action::ViewSide, KeyCode::Numpad3),
+            key(Action::ViewTop, KeyCode::Numpad7),
+            key(Action::ToggleProjection, KeyCode::Numpad5),
+            key(Action::PlayPath, KeyCode::KeyP),
+            key(Action::RecordPath, KeyCode::KeyR),
+            key(Action::NextToneMapping, KeyCode::KeyT),
+            key(Action::ExposureDown, KeyCode::Minus),
+            key(Action::ExposureUp, KeyCode::Equal),
+            key(Action::ToggleSsao, KeyCode::KeyO),
+            key(Action::NextUpscaleFilter, KeyCode::KeyU),
+            key(Action::Rebind, KeyCode::F9),
+            key(Action::Exit, KeyCode::Escape)
+        ];
+        let function_keys = [
+            KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4,
+            KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8
+        ];
+        actions.extend(function_keys.iter().enumerate().map(|(i, f)| key(Action::ToggleEffect(i), *f)));
+
+        let axis = |axis, source| (axis, AxisBinding { source, scale: 1.0 });
+        Self {
+            actions,
+            axes: vec![
+                axis(Axis::LookX, AxisSource::MouseX),
+                axis(Axis::LookY, AxisSource::MouseY),
+                axis(Axis::Zoom, AxisSource::Wheel)
+            ]
+        }
+    }
+}
+
+impl InputMap {
+    // actions and axes named in the file replace their default bindings,
+    // anything left out keeps them
+    pub fn from_json(config: &Value) -> Self {
+        let mut map = Self::default();
+        for (name, bindings) in config["actions"].as_object().into_iter().flatten() {
+            let Some(action) = Action::from_name(name) else {
+                log::warn!("Unknown input action: {}", name);
+                continue;
+            };
+            map.actions.retain(|(a, _)| *a != action);
+            for binding in bindings.as_array().into_iter().flatten().filter_map(|b| b.as_str()) {
+                match Binding::from_name(binding) {
+                    Some(binding) => map.actions.push((action, binding)),
+                    None => log::warn!("Unknown binding for {}: {}", name, binding)
+                }
+            }
+        }
+        for (name, bindings) in config["axes"].as_object().into_iter().flatten() {
+            let Some(axis) = Axis::from_name(name) else {
+                log::warn!("Unknown input axis: {}", name);
+                continue;
+            };
+            map.axes.retain(|(a, _)| *a != axis);
+            for binding in bindings.as_array().into_iter().flatten().filter_map(|b| b.as_str()) {
+                match AxisBinding::from_name(binding) {
+                    Some(binding) => map.axes.push((axis, binding)),
+                    None => log::warn!("Unknown binding for {}: {}", name, binding)
+                }
+            }
+        }
+        map
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut actions = serde_json::Map::new();
+        for (action, binding) in &self.actions {
+            let bindings = actions.entry(action.name()).or_insert_with(|| json!([]));
+            bindings.as_array_mut().unwrap().push(json!(binding.name()));
+        }
+        let mut axes = serde_json::Map::new();
+        for (axis, binding) in &self.axes {
+            let bindings = axes.entry(axis.name()).or_insert_with(|| json!([]));
+            bindings.as_array_mut().unwrap().push(json!(binding.name()));
+        }
+        json!({ "actions": actions, "axes": axes })
+    }
+
+    fn actions(&self, binding: Binding) -> Vec<Action> {
+        self.actions.iter().filter(|(_, b)| *b == binding).map(|(action, _)| *action).collect()
+    }
+
+    // moves every action on one binding over to another. refused when the
+    // new binding already drives something, one key doing two things at
+    // once is never what was meant
+    pub fn rebind(&mut self, from: Binding, to: Binding) -> anyhow::Result<()> {
+        let taken = self.actions(to);
+        if from != to && !taken.is_empty() {
+            let names: Vec<&str> = taken.iter().map(|action| action.name()).collect();
+            anyhow::bail!("{} is already bound to {}", to.name(), names.join(", "));
+        }
+        for (_, binding) in self.actions.iter_mut().filter(|(_, b)| *b == from) {
+            *binding = to;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    // pressed or released, key repeats are dropped
+    Action(Action, bool),
+    Axis(Axis, f32)
+}
+
+// rebinding takes the binding to replace, then its replacement
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rebinding {
+    Idle,
+    WaitingForOld,
+    WaitingForNew(Binding)
+}
+
+pub struct Input {
+    map: InputMap,
+    rebinding: Rebinding
+}
+
+impl Input {
+    pub fn new(map: InputMap) -> Self {
+        Self {
+            map,
+            rebinding: Rebinding::Idle
+        }
+    }
+
+    // the actions and axes a window event maps to, empty when nothing
+    // is bound to it
+    pub fn window_event(&mut self, event: &WindowEvent) -> Vec<InputEvent> {
+        let (binding, state) = match event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent { physical_key: PhysicalKey::Code(key), state, repeat: false, .. },
+                ..
+            } => (Binding::Key(*key), *state),
+            WindowEvent::MouseInput { button, state, .. } => (Binding::Mouse(*button), *state),
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    // roughly one line per 50 pixels
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0
+                };
+                return self.axis_events(AxisSource::Wheel, lines);
+            }
+            _ => return Vec::new()
+        };
+        let pressed = state == ElementState::Pressed;
+
+        // releases still go through, a key held when rebinding started
+        // would stay held otherwise
+        if self.rebinding != Rebinding::Idle && pressed {
+            self.rebind(binding);
+            return vec![];
+        }
+        self.map.actions(binding).into_iter()
+            .map(|action| InputEvent::Action(action, pressed))
+            .collect()
+    }
+
+    pub fn mouse_motion(&self, delta: (f64, f64)) -> Vec<InputEvent> {
+        let mut events = self.axis_events(AxisSource::MouseX, delta.0 as f32);
+        events.extend(self.axis_events(AxisSource::MouseY, delta.1 as f32));
+        events
+    }
+
+    fn axis_events(&self, source: AxisSource, value: f32) -> Vec<InputEvent> {
+        self.map.axes.iter()
+            .filter(|(_, binding)| binding.source == source)
+            .map(|(axis, binding)| InputEvent::Axis(*axis, value * binding.scale))
+            .collect()
+    }
+
+    // the next two presses pick the binding to replace and its replacement,
+    // pressing the rebind binding again cancels
+    pub fn start_rebinding(&mut self) {
+        log::info!("Rebinding, press the key or button to replace");
+        self.rebinding = Rebinding::WaitingForOld;
+    }
+
+    fn rebind(&mut self, binding: Binding) {
+        if self.map.actions(binding).contains(&Action::Rebind) {
+            log::info!("Rebinding cancelled");
+            self.rebinding = Rebinding::Idle;
+            return;
+        }
+        match self.rebinding {
+            Rebinding::WaitingForOld => {
+                let actions = self.map.actions(binding);
+                if actions.is_empty() {
+                    log::info!("Nothing is bound to {}", binding.name());
+                    return;
+                }
+                let names: Vec<&str> = actions.iter().map(|action| action.name()).collect();
+                log::info!("Press the new binding for {}", names.join(", "));
+                self.rebinding = Rebinding::WaitingForNew(binding);
+            }
+            Rebinding::WaitingForNew(old) => {
+                if let Err(e) = self.map.rebind(old, binding) {
+                    log::warn!("{}, press another binding", e);
+                    return;
+                }
+                log::info!("Rebound {} to {}", old.name(), binding.name());
+                self.rebinding = Rebinding::Idle;
+                match resources::save_input_map(&self.map) {
+                    Ok(file) => log::info!("Saved input bindings to {}", file.display()),
+                    Err(e) => log::warn!("Couldn't save input bindings: {}", e)
+                }
+            }
+            Rebinding::Idle => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinding_onto_a_used_binding_is_refused() {
+        let mut map = InputMap::default();
+        let w = Binding::Key(KeyCode::KeyW);
+        assert!(map.rebind(w, Binding::Key(KeyCode::KeyS)).is_err());
+        assert_eq!(map.actions(w), vec![Action::MoveForward]);
+        map.rebind(w, Binding::Key(KeyCode::KeyI)).unwrap();
+        assert!(map.actions(w).is_empty());
+        assert_eq!(map.actions(Binding::Key(KeyCode::KeyI)), vec![Action::MoveForward]);
+    }
+
+    #[test]
+    fn releases_go_through_while_rebinding() {
+        let mut input = Input::new(InputMap::default());
+        let click = |state| WindowEvent::MouseInput {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            state,
+            button: MouseButton::Left
+        };
+        input.start_rebinding();
+        assert!(input.window_event(&click(ElementState::Pressed)).is_empty());
+        assert_eq!(
+            input.window_event(&click(ElementState::Released)),
+            vec![InputEvent::Action(Action::Rotate, false)]
+        );
+    }
+}
@@ -7,3 +7,11 @@ mod resources;
 mod pipeline;
 mod resolution;
 mod camera;
+mod camera_path;
+mod input;
+mod replay;
+mod timestep;
+mod culling;
+mod settings;
+mod postprocess;
+mod ssao;
@@ -1,4 +1,5 @@
 use std::ops::Range;
+use cgmath::{Point3, Matrix4, Transform, EuclideanSpace, InnerSpace};
 
 pub trait Vertex {
     fn desc() -> wgpu::VertexBufferLayout<'static>;
@@ -39,35 +40,133 @@ impl Vertex for ModelVertex {
     }
 }
 
+// axis aligned bounding box, in model space on meshes
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>
+}
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[ModelVertex]) -> Self {
+        let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        for vertex in vertices {
+            let [x, y, z] = vertex.position;
+            min = Point3::new(min.x.min(x), min.y.min(y), min.z.min(z));
+            max = Point3::new(max.x.max(x), max.y.max(y), max.z.max(z));
+        }
+        // empty meshes collapse to the origin
+        if vertices.is_empty() {
+            min = Point3::origin();
+            max = Point3::origin();
+        }
+        Self { min, max }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: Point3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Point3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z))
+        }
+    }
+
+    // the box around the transformed corners, so it only ever grows
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let corners = (0..8).map(|i| Point3::new(
+            if i & 1 == 0 { self.min.x } else { self.max.x },
+            if i & 2 == 0 { self.min.y } else { self.max.y },
+            if i & 4 == 0 { self.min.z } else { self.max.z }
+        ));
+        let mut corners = corners.map(|corner| matrix.transform_point(corner));
+        let first = corners.next().unwrap();
+        corners.fold(Self { min: first, max: first }, |bounds, corner| {
+            bounds.union(&Self { min: corner, max: corner })
+        })
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    // radius of the sphere through the corners
+    pub fn radius(&self) -> f32 {
+        (self.max - self.min).magnitude() * 0.5
+    }
+}
+
+// tighter than the sphere around the box, centered on the box but only
+// reaching the furthest vertex
+#[derive(Copy, Clone, Debug)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32
+}
+
+impl BoundingSphere {
+    pub fn from_vertices(vertices: &[ModelVertex], bounds: &Aabb) -> Self {
+        let center = bounds.center();
+        let radius = vertices.iter()
+            .map(|vertex| (Point3::from(vertex.position) - center).magnitude())
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    // scaled by the largest axis scale, so uneven scaling only grows it
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let scale = matrix.x.truncate().magnitude()
+            .max(matrix.y.truncate().magnitude())
+            .max(matrix.z.truncate().magnitude());
+        Self {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * scale
+        }
+    }
+}
+
 pub struct Model {
     pub meshes: Vec<Mesh>,
 }
 
+impl Model {
+    pub fn bounds(&self) -> Aabb {
+        self.meshes.iter()
+            .map(|mesh| mesh.bounds)
+            .reduce(|bounds, mesh_bounds| bounds.union(&mesh_bounds))
+            .unwrap_or(Aabb { min: Point3::origin(), max: Point3::origin() })
+    }
+}
+
 pub struct Mesh {
     pub _name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
+    pub material: usize,
+    pub bounds: Aabb,
+    pub sphere: BoundingSphere
 }
 
 pub trait DrawModel<'a> {
-    fn draw_mesh_instanced(
-        &mut self,
-        mesh: &'a Mesh,
-        instances: Range<u32>
-    );
-    fn draw_model(
+    fn draw_mesh(
         &mut self,
-        model: &'a Model
+        mesh: &'a Mesh
     );
-    fn draw_model_instanced(
+    fn draw_mesh_instanced(
         &mut self,
-        model: &'a Model,
+        mesh: &'a Mesh,
         instances: Range<u32>
     );
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a> where 'b: 'a {
+    fn draw_mesh(
+            &mut self,
+            mesh: &'b Mesh
+        ) {
+        self.draw_mesh_instanced(mesh, 0..1);
+    }
+
     fn draw_mesh_instanced(
             &mut self,
             mesh: &'b Mesh,
@@ -77,21 +176,4 @@ impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a> where 'b: 'a {
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
-
-    fn draw_model(
-        &mut self,
-        model: &'b Model,
-    ) {
-        self.draw_model_instanced(model, 0..1);
-    }
-
-    fn draw_model_instanced(
-        &mut self,
-        model: &'b Model,
-        instances: Range<u32>
-    ) {
-        for mesh in &model.meshes {
-            self.draw_mesh_instanced(mesh, instances.clone());
-        }
-    }
 }
@@ -1,3 +1,29 @@
+// reverse z puts the near plane at 1 and infinity at 0, float precision
+// then spreads out over distance instead of bunching up near the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DepthMode {
+    Standard,
+    Reverse
+}
+
+impl DepthMode {
+    pub fn compare(self) -> wgpu::CompareFunction {
+        match self {
+            DepthMode::Standard => wgpu::CompareFunction::LessEqual,
+            DepthMode::Reverse => wgpu::CompareFunction::GreaterEqual
+        }
+    }
+
+    // what the depth buffer is cleared to, nothing drawn is the far plane
+    pub fn far_depth(self) -> f32 {
+        match self {
+            DepthMode::Standard => 1.0,
+            DepthMode::Reverse => 0.0
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
@@ -6,7 +32,10 @@ pub fn create_render_pipeline(
     vertex_layouts: &[wgpu::VertexBufferLayout],
     shader: wgpu::ShaderModuleDescriptor,
     label: &str,
-    depth_write: bool
+    depth_write: bool,
+    depth_mode: DepthMode,
+    // has to match the sample count of the attachments
+    sample_count: u32
 )  -> wgpu::RenderPipeline {
     let shader = device.create_shader_module(shader);
 
@@ -44,12 +73,124 @@ pub fn create_render_pipeline(
         depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
             format,
             depth_write_enabled: depth_write,
-            depth_compare: wgpu::CompareFunction::LessEqual,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None
+    })
+}
+
+// depth only, used to lay down the depth buffer before the lit passes
+#[allow(clippy::too_many_arguments)]
+pub fn create_depth_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    depth_format: wgpu::TextureFormat,
+    vertex_layouts: &[wgpu::VertexBufferLayout],
+    shader: wgpu::ShaderModuleDescriptor,
+    label: &str,
+    depth_mode: DepthMode,
+    sample_count: u32
+) -> wgpu::RenderPipeline {
+    let shader = device.create_shader_module(shader);
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: vertex_layouts,
+            compilation_options: Default::default(),
+        },
+        fragment: None,
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare: depth_mode.compare(),
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+        cache: None
+    })
+}
+
+// draws the far plane over everything in the viewport whatever is there,
+// a clear limited to the viewport. the color format has to match the
+// pass it is used in but nothing is written to it
+pub fn create_depth_clear_pipeline(
+    device: &wgpu::Device,
+    color_format: Option<wgpu::TextureFormat>,
+    depth_format: wgpu::TextureFormat,
+    depth_mode: DepthMode,
+    sample_count: u32
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Depth Clear Pipeline Layout"),
+        bind_group_layouts: &[],
+        push_constant_ranges: &[]
+    });
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Depth Clear Shader"),
+        source: wgpu::ShaderSource::Wgsl(format!(
+            "const FAR_DEPTH: f32 = {:?};\n{}",
+            depth_mode.far_depth(),
+            include_str!("shaders/depth_clear.wgsl")
+        ).into())
+    });
+    let targets = [color_format.map(|format| wgpu::ColorTargetState {
+        format,
+        blend: None,
+        write_mask: wgpu::ColorWrites::empty()
+    })];
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Depth Clear Pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: color_format.map(|_| wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &targets,
+            compilation_options: Default::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: depth_format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Always,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         }),
         multisample: wgpu::MultisampleState {
-            count: 1,
+            count: sample_count,
             mask: !0,
             alpha_to_coverage_enabled: false,
         },
@@ -57,3 +198,21 @@ pub fn create_render_pipeline(
         cache: None
     })
 }
+
+// msaa is only used when every attachment format supports the requested
+// count, anything else falls back to no multisampling
+pub fn supported_sample_count(
+    adapter: &wgpu::Adapter,
+    formats: &[wgpu::TextureFormat],
+    requested: u32
+) -> u32 {
+    let supported = formats.iter().all(|format| {
+        adapter.get_texture_format_features(*format).flags.sample_count_supported(requested)
+    });
+    if supported {
+        requested
+    } else {
+        log::warn!("{}x msaa isn't supported by the adapter, falling back to 1x", requested);
+        1
+    }
+}
@@ -0,0 +1,232 @@
+use super::{
+    smaa,
+    Effect,
+    EffectParameters,
+    create_target,
+    create_input_layout,
+    create_input_bind_group,
+    create_effect_pipeline,
+    fullscreen_pass,
+    super::textures::texture
+};
+use serde_json::Value;
+
+// the discriminant is what gets written to the mode parameter
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AntiAliasing {
+    Fxaa = 0,
+    Smaa = 1
+}
+
+impl AntiAliasing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fxaa" => Some(AntiAliasing::Fxaa),
+            "smaa" => Some(AntiAliasing::Smaa),
+            _ => None
+        }
+    }
+
+    pub fn from_mode(mode: f32) -> Self {
+        match mode as u32 {
+            0 => AntiAliasing::Fxaa,
+            _ => AntiAliasing::Smaa
+        }
+    }
+}
+
+struct SmaaTargets {
+    edges: texture::Texture,
+    edges_bind_group: wgpu::BindGroup,
+    weights: texture::Texture,
+    weights_bind_group: wgpu::BindGroup
+}
+
+// post process anti aliasing on the internal resolution image, fxaa in a
+// single pass or smaa 1x as edge detection, blend weights and neighbourhood
+// blending. smaa runs with corner detection but without diagonal detection
+pub struct AntiAliasEffect {
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    area_bind_group: wgpu::BindGroup,
+    search_bind_group: wgpu::BindGroup,
+    parameters: EffectParameters,
+    fxaa_pipeline: wgpu::RenderPipeline,
+    edges_pipeline: wgpu::RenderPipeline,
+    weights_pipeline: wgpu::RenderPipeline,
+    blend_pipeline: wgpu::RenderPipeline,
+    targets: Option<SmaaTargets>
+}
+
+impl AntiAliasEffect {
+    pub const NAME: &'static str = "antialias";
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let texture_layout = create_input_layout(device);
+        // smaa reads two edges with one fetch and interpolates its areas
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let area = texture::Texture::from_rgba8(
+            device, queue, &smaa::area_texture(), (smaa::AREA_SIZE, smaa::AREA_SIZE), false, Some("Smaa Area Texture")
+        ).unwrap();
+        let search = create_search_texture(device, queue);
+        let area_bind_group = create_input_bind_group(device, &texture_layout, &sampler, &area.view);
+        let search_bind_group = create_input_bind_group(device, &texture_layout, &sampler, &search);
+        // edge threshold is relative to the local luma for fxaa and
+        // absolute for smaa, subpixel only applies to fxaa and max_search,
+        // in steps of two pixels, only to smaa
+        let parameters = EffectParameters::new(device, &[
+            ("mode", AntiAliasing::Fxaa as u32 as f32),
+            ("edge_threshold", 0.125),
+            ("subpixel", 0.75),
+            ("max_search", 16.0)
+        ], &["edge_threshold", "subpixel", "max_search"]);
+
+        let common = include_str!("../shaders/antialias_common.wgsl");
+        // every pass reads its input and the parameters, the weights pass
+        // also the area and search textures and blending the weights
+        let create_pipeline = |source: &str, textures: usize, label| {
+            let mut layouts = vec![&texture_layout, &parameters.layout];
+            layouts.extend(std::iter::repeat_n(&texture_layout, textures));
+            create_effect_pipeline(device, &layouts, &format!("{}\n{}", common, source), label)
+        };
+        let fxaa_pipeline = create_pipeline(include_str!("../shaders/fxaa.wgsl"), 0, "Fxaa Pipeline");
+        let edges_pipeline = create_pipeline(
+            include_str!("../shaders/smaa_edges.wgsl"), 0, "Smaa Edges Pipeline"
+        );
+        let weights_pipeline = create_pipeline(
+            include_str!("../shaders/smaa_weights.wgsl"), 2, "Smaa Weights Pipeline"
+        );
+        let blend_pipeline = create_pipeline(
+            include_str!("../shaders/smaa_blend.wgsl"), 1, "Smaa Blend Pipeline"
+        );
+
+        Self {
+            texture_layout,
+            sampler,
+            area_bind_group,
+            search_bind_group,
+            parameters,
+            fxaa_pipeline,
+            edges_pipeline,
+            weights_pipeline,
+            blend_pipeline,
+            targets: None
+        }
+    }
+}
+
+impl Effect for AntiAliasEffect {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn configure(&mut self, settings: &Value) {
+        self.parameters.configure(settings);
+        if let Some(name) = settings["mode"].as_str() {
+            match AntiAliasing::from_name(name) {
+                Some(anti_aliasing) => self.parameters.set("mode", anti_aliasing as u32 as f32),
+                None => log::warn!("Unknown anti aliasing mode: {}", name)
+            }
+        }
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        self.parameters.get(name)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        self.parameters.set(name, value);
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, resolution: (u32, u32)) {
+        let edges = create_target(device, resolution, "Smaa Edges Texture");
+        let weights = create_target(device, resolution, "Smaa Weights Texture");
+        self.targets = Some(SmaaTargets {
+            edges_bind_group: create_input_bind_group(device, &self.texture_layout, &self.sampler, &edges.view),
+            weights_bind_group: create_input_bind_group(device, &self.texture_layout, &self.sampler, &weights.view),
+            edges,
+            weights
+        });
+    }
+
+    fn apply(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView
+    ) {
+        let Some(targets) = self.targets.as_ref() else {
+            return;
+        };
+        self.parameters.upload(queue);
+        let parameters = &self.parameters.bind_group;
+
+        match AntiAliasing::from_mode(self.parameters.get("mode").unwrap_or(0.0)) {
+            AntiAliasing::Fxaa => fullscreen_pass(
+                encoder,
+                "Fxaa Pass",
+                &self.fxaa_pipeline,
+                &[input, parameters],
+                output
+            ),
+            AntiAliasing::Smaa => {
+                fullscreen_pass(
+                    encoder,
+                    "Smaa Edges Pass",
+                    &self.edges_pipeline,
+                    &[input, parameters],
+                    &targets.edges.view
+                );
+                fullscreen_pass(
+                    encoder,
+                    "Smaa Weights Pass",
+                    &self.weights_pipeline,
+                    &[&targets.edges_bind_group, parameters, &self.area_bind_group, &self.search_bind_group],
+                    &targets.weights.view
+                );
+                fullscreen_pass(
+                    encoder,
+                    "Smaa Blend Pass",
+                    &self.blend_pipeline,
+                    &[input, parameters, &targets.weights_bind_group],
+                    output
+                );
+            }
+        }
+    }
+}
+
+// the search texture only has the one channel, Texture::from_rgba8 can't
+// hold it
+fn create_search_texture(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::TextureView {
+    use wgpu::util::DeviceExt;
+    let (width, height) = smaa::SEARCH_SIZE;
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: Some("Smaa Search Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[]
+        },
+        wgpu::util::TextureDataOrder::LayerMajor,
+        &smaa::search_texture()
+    );
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
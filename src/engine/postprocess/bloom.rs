@@ -0,0 +1,208 @@
+use super::{
+    Effect,
+    EffectParameters,
+    create_input_layout,
+    create_input_bind_group,
+    create_effect_pipeline,
+    fullscreen_pass,
+    super::textures::texture
+};
+use serde_json::Value;
+
+// the smallest mip has to keep a few texels for the filters to work with
+const MIN_MIP_SIZE: u32 = 4;
+const MAX_MIPS: u32 = 6;
+
+struct MipChain {
+    // bright pass and progressive downsamples, the first mip is half resolution
+    down_views: Vec<wgpu::TextureView>,
+    down_bind_groups: Vec<wgpu::BindGroup>,
+    // upsampled sums of every smaller mip, one level shorter than the down chain
+    up_views: Vec<wgpu::TextureView>,
+    up_bind_groups: Vec<wgpu::BindGroup>
+}
+
+// bright pass, downsample into a mip chain, then tent filtered upsampling
+// back up, with the sum added onto the hdr scene before tone mapping
+pub struct BloomEffect {
+    texture_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    parameters: EffectParameters,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    mips: Option<MipChain>
+}
+
+impl BloomEffect {
+    pub const NAME: &'static str = "bloom";
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        let texture_layout = create_input_layout(device);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        // levels is filled in from the mip chain rather than the settings
+        let parameters = EffectParameters::new(device, &[
+            ("threshold", 1.0),
+            ("knee", 0.5),
+            ("intensity", 0.3),
+            ("radius", 1.0),
+            ("levels", 1.0)
+        ], &["threshold", "knee", "intensity", "radius"]);
+
+        let common = include_str!("../shaders/bloom_common.wgsl");
+        let create_pipeline = |source: &str, label| create_effect_pipeline(
+            device,
+            &[&texture_layout, &parameters.layout, &texture_layout],
+            &format!("{}\n{}", common, source),
+            label
+        );
+        let prefilter_pipeline = create_pipeline(
+            include_str!("../shaders/bloom_prefilter.wgsl"), "Bloom Prefilter Pipeline"
+        );
+        let downsample_pipeline = create_pipeline(
+            include_str!("../shaders/bloom_downsample.wgsl"), "Bloom Downsample Pipeline"
+        );
+        let upsample_pipeline = create_pipeline(
+            include_str!("../shaders/bloom_upsample.wgsl"), "Bloom Upsample Pipeline"
+        );
+        let composite_pipeline = create_pipeline(
+            include_str!("../shaders/bloom_composite.wgsl"), "Bloom Composite Pipeline"
+        );
+
+        Self {
+            texture_layout,
+            sampler,
+            parameters,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+            mips: None
+        }
+    }
+}
+
+impl Effect for BloomEffect {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn configure(&mut self, settings: &Value) {
+        self.parameters.configure(settings);
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        self.parameters.get(name)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        self.parameters.set(name, value);
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, resolution: (u32, u32)) {
+        let size = ((resolution.0 / 2).max(1), (resolution.1 / 2).max(1));
+        let mip_count = (size.0.min(size.1) / MIN_MIP_SIZE).max(1).ilog2().clamp(1, MAX_MIPS - 1) + 1;
+        self.parameters.set("levels", mip_count as f32);
+
+        let create_chain = |label, mip_level_count| device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1
+            },
+            dimension: wgpu::TextureDimension::D2,
+            format: texture::Texture::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            sample_count: 1,
+            mip_level_count,
+            view_formats: &[]
+        });
+        let mip_views = |texture: &wgpu::Texture, count| (0..count).map(|mip| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        }).collect::<Vec<_>>();
+
+        let down_texture = create_chain("Bloom Downsample Texture", mip_count);
+        let up_texture = create_chain("Bloom Upsample Texture", mip_count - 1);
+        let down_views = mip_views(&down_texture, mip_count);
+        let up_views = mip_views(&up_texture, mip_count - 1);
+        let bind_groups = |views: &Vec<wgpu::TextureView>| views.iter()
+            .map(|view| create_input_bind_group(device, &self.texture_layout, &self.sampler, view))
+            .collect();
+
+        self.mips = Some(MipChain {
+            down_bind_groups: bind_groups(&down_views),
+            up_bind_groups: bind_groups(&up_views),
+            down_views,
+            up_views
+        });
+    }
+
+    fn apply(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView
+    ) {
+        let Some(mips) = self.mips.as_ref() else {
+            return;
+        };
+        self.parameters.upload(queue);
+        let parameters = &self.parameters.bind_group;
+        let last = mips.down_views.len() - 1;
+
+        // the third group is unused by the down passes, any texture will do
+        fullscreen_pass(
+            encoder,
+            "Bloom Prefilter Pass",
+            &self.prefilter_pipeline,
+            &[input, parameters, &mips.down_bind_groups[last]],
+            &mips.down_views[0]
+        );
+        for mip in 1..=last {
+            fullscreen_pass(
+                encoder,
+                "Bloom Downsample Pass",
+                &self.downsample_pipeline,
+                &[&mips.down_bind_groups[mip - 1], parameters, &mips.down_bind_groups[0]],
+                &mips.down_views[mip]
+            );
+        }
+        // each level adds the upsampled level below it to its own downsample
+        for mip in (0..last).rev() {
+            let smaller = if mip + 1 == last {
+                &mips.down_bind_groups[last]
+            } else {
+                &mips.up_bind_groups[mip + 1]
+            };
+            fullscreen_pass(
+                encoder,
+                "Bloom Upsample Pass",
+                &self.upsample_pipeline,
+                &[smaller, parameters, &mips.down_bind_groups[mip]],
+                &mips.up_views[mip]
+            );
+        }
+        fullscreen_pass(
+            encoder,
+            "Bloom Composite Pass",
+            &self.composite_pipeline,
+            &[input, parameters, &mips.up_bind_groups[0]],
+            output
+        );
+    }
+}
@@ -0,0 +1,433 @@
+pub mod tonemap;
+pub mod bloom;
+pub mod palette;
+pub mod antialias;
+pub mod smaa;
+
+use super::{
+    textures::texture,
+    pipeline,
+    settings
+};
+use wgpu::util::DeviceExt;
+use serde_json::Value;
+
+// a stage of the post processing chain, reads the previous stage through
+// the shared input bind group (group 0) and renders into the next target
+pub trait Effect {
+    fn name(&self) -> &str;
+
+    // picks up the effect's block from the settings file, anything it
+    // names that has to be loaded is loaded up front and passed in
+    fn configure(&mut self, settings: &Value);
+
+    fn parameter(&self, name: &str) -> Option<f32>;
+    fn set_parameter(&mut self, name: &str, value: f32);
+
+    // only effects with their own render targets care about this
+    fn resize(&mut self, _device: &wgpu::Device, _resolution: (u32, u32)) {}
+
+    fn apply(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView
+    );
+}
+
+struct ChainEntry {
+    effect: Box<dyn Effect>,
+    enabled: bool
+}
+
+// the scene is rendered into the first target, enabled effects then
+// bounce between the other two in the order they were registered
+pub struct PostProcessChain {
+    input_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    targets: [texture::Texture; 3],
+    input_bind_groups: [wgpu::BindGroup; 3],
+    effects: Vec<ChainEntry>,
+    resolution: (u32, u32)
+}
+
+impl PostProcessChain {
+    pub fn new(device: &wgpu::Device, resolution: (u32, u32)) -> Self {
+        let input_layout = create_input_layout(device);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let targets = create_targets(device, resolution);
+        let input_bind_groups = create_input_bind_groups(device, &input_layout, &sampler, &targets);
+
+        Self {
+            input_layout,
+            sampler,
+            targets,
+            input_bind_groups,
+            effects: Vec::new(),
+            resolution
+        }
+    }
+
+    pub fn input_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.input_layout
+    }
+
+    // where the scene gets rendered
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+
+    pub fn target_views(&self) -> [&wgpu::TextureView; 3] {
+        [&self.targets[0].view, &self.targets[1].view, &self.targets[2].view]
+    }
+
+    // recreates the targets and lets every effect recreate its own, the
+    // bind groups pointing at the old targets have to be rebuilt by the caller
+    pub fn resize(&mut self, device: &wgpu::Device, resolution: (u32, u32)) {
+        self.resolution = resolution;
+        self.targets = create_targets(device, resolution);
+        self.input_bind_groups = create_input_bind_groups(device, &self.input_layout, &self.sampler, &self.targets);
+        for entry in self.effects.iter_mut() {
+            entry.effect.resize(device, resolution);
+        }
+    }
+
+    pub fn register(&mut self, device: &wgpu::Device, mut effect: Box<dyn Effect>) {
+        effect.resize(device, self.resolution);
+        self.effects.push(ChainEntry { effect, enabled: true });
+    }
+
+    pub fn configure(&mut self, settings: &settings::Settings) {
+        for entry in self.effects.iter_mut() {
+            let effect_settings = settings.effect(entry.effect.name());
+            entry.enabled = effect_settings["enabled"].as_bool().unwrap_or(entry.enabled);
+            entry.effect.configure(effect_settings);
+        }
+    }
+
+    pub fn effect_mut(&mut self, name: &str) -> Option<&mut Box<dyn Effect>> {
+        self.effects.iter_mut()
+            .find(|entry| entry.effect.name() == name)
+            .map(|entry| &mut entry.effect)
+    }
+
+    // flips the effect at a position in the chain, returning its name and new state
+    pub fn toggle(&mut self, index: usize) -> Option<(&str, bool)> {
+        let entry = self.effects.get_mut(index)?;
+        entry.enabled = !entry.enabled;
+        Some((entry.effect.name(), entry.enabled))
+    }
+
+    // runs every enabled effect and returns the index of the target
+    // holding the final image
+    pub fn run(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) -> usize {
+        let mut current = 0;
+        for entry in self.effects.iter_mut().filter(|entry| entry.enabled) {
+            let next = if current == 1 { 2 } else { 1 };
+            entry.effect.apply(queue, encoder, &self.input_bind_groups[current], &self.targets[next].view);
+            current = next;
+        }
+        current
+    }
+}
+
+fn create_targets(device: &wgpu::Device, resolution: (u32, u32)) -> [texture::Texture; 3] {
+    ["Scene Target", "Post Process Ping Target", "Post Process Pong Target"]
+        .map(|label| create_target(device, resolution, label))
+}
+
+// intermediate color target at the internal resolution
+pub fn create_target(device: &wgpu::Device, resolution: (u32, u32), label: &str) -> texture::Texture {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: resolution.0,
+            height: resolution.1,
+            depth_or_array_layers: 1
+        },
+        dimension: wgpu::TextureDimension::D2,
+        format: texture::Texture::HDR_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        sample_count: 1,
+        mip_level_count: 1,
+        view_formats: &[]
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+    texture::Texture { texture, view, sampler }
+}
+
+// a texture and a filtering sampler, how effects read the previous stage
+pub fn create_input_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+        label: Some("Post Process Input Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None
+            }
+        ]
+    })
+}
+
+fn create_input_bind_groups(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    targets: &[texture::Texture; 3]
+) -> [wgpu::BindGroup; 3] {
+    [0, 1, 2].map(|i| create_input_bind_group(device, layout, sampler, &targets[i].view))
+}
+
+pub fn create_input_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    view: &wgpu::TextureView
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Post Process Input Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view)
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler)
+            }
+        ]
+    })
+}
+
+// effect shaders get the fullscreen vertex shader and input bindings
+// from fullscreen.wgsl and post_common.wgsl prepended
+pub fn create_effect_pipeline(
+    device: &wgpu::Device,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    source: &str,
+    label: &str
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts,
+        push_constant_ranges: &[]
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(
+            format!(
+                "{}\n{}\n{}",
+                include_str!("../shaders/fullscreen.wgsl"),
+                include_str!("../shaders/post_common.wgsl"),
+                source
+            ).into()
+        )
+    };
+    pipeline::create_render_pipeline(
+        device, &layout, texture::Texture::HDR_FORMAT, None, &[], shader, label, false, pipeline::DepthMode::Standard, 1
+    )
+}
+
+pub fn fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_groups: &[&wgpu::BindGroup],
+    output: &wgpu::TextureView
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store
+            }
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None
+    });
+    render_pass.set_pipeline(pipeline);
+    for (index, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(index as u32, bind_group, &[]);
+    }
+    render_pass.draw(0..3, 0..1);
+}
+
+// a block of f32 parameters in a uniform buffer, declared in the same
+// order as the fields of the matching shader struct
+pub struct EffectParameters {
+    values: Vec<(&'static str, f32)>,
+    configurable: Vec<&'static str>,
+    buffer: wgpu::Buffer,
+    pub layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    dirty: bool
+}
+
+impl EffectParameters {
+    // only the configurable names are read from the settings, the rest are
+    // derived by the effect and changed through set
+    pub fn new(device: &wgpu::Device, values: &[(&'static str, f32)], configurable: &[&'static str]) -> Self {
+        let layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+            label: Some("Effect Parameter Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None
+                    },
+                    count: None
+                }
+            ]
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Effect Parameter Buffer"),
+            contents: bytemuck::cast_slice(&pack_parameters(values)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Effect Parameter Bind Group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        Self {
+            values: values.to_vec(),
+            configurable: configurable.to_vec(),
+            buffer,
+            layout,
+            bind_group,
+            dirty: false
+        }
+    }
+
+    // numbers in the settings block override the parameters of the same name
+    pub fn configure(&mut self, settings: &Value) {
+        for (name, value) in self.values.iter_mut().filter(|(name, _)| self.configurable.contains(name)) {
+            if let Some(setting) = settings[*name].as_f64() {
+                *value = setting as f32;
+                self.dirty = true;
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        self.values.iter().find(|(n, _)| *n == name).map(|(_, value)| *value)
+    }
+
+    pub fn set(&mut self, name: &str, value: f32) {
+        if let Some((_, parameter)) = self.values.iter_mut().find(|(n, _)| *n == name) {
+            *parameter = value;
+            self.dirty = true;
+        }
+    }
+
+    // writes changed values before the effect renders
+    pub fn upload(&mut self, queue: &wgpu::Queue) {
+        if self.dirty {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&pack_parameters(&self.values)));
+            self.dirty = false;
+        }
+    }
+}
+
+// uniform buffers are sized in vec4 steps
+fn pack_parameters(parameters: &[(&'static str, f32)]) -> Vec<f32> {
+    let mut packed: Vec<f32> = parameters.iter().map(|(_, value)| *value).collect();
+    packed.resize(packed.len().div_ceil(4).max(1) * 4, 0.0);
+    packed
+}
+
+// the common kind of effect, one fullscreen pass with its parameters in group 1
+pub struct ShaderEffect {
+    name: String,
+    pipeline: wgpu::RenderPipeline,
+    parameters: EffectParameters
+}
+
+impl ShaderEffect {
+    pub fn new(
+        device: &wgpu::Device,
+        input_layout: &wgpu::BindGroupLayout,
+        name: &str,
+        source: &str,
+        parameters: &[(&'static str, f32)],
+        configurable: &[&'static str]
+    ) -> Self {
+        let parameters = EffectParameters::new(device, parameters, configurable);
+        let pipeline = create_effect_pipeline(
+            device, &[input_layout, &parameters.layout], source, &format!("{} Effect Pipeline", name)
+        );
+
+        Self {
+            name: name.to_string(),
+            pipeline,
+            parameters
+        }
+    }
+}
+
+impl Effect for ShaderEffect {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn configure(&mut self, settings: &Value) {
+        self.parameters.configure(settings);
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        self.parameters.get(name)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        self.parameters.set(name, value);
+    }
+
+    fn apply(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView
+    ) {
+        self.parameters.upload(queue);
+        fullscreen_pass(encoder, &self.name, &self.pipeline, &[input, &self.parameters.bind_group], output);
+    }
+}
@@ -0,0 +1,177 @@
+use super::{
+    Effect,
+    EffectParameters,
+    create_effect_pipeline,
+    fullscreen_pass,
+    super::textures::texture
+};
+use serde_json::Value;
+
+// shaders loop over the palette for every pixel
+const MAX_COLORS: usize = 256;
+
+// used until a palette file is configured
+const DEFAULT_PALETTE: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], [0x1d, 0x2b, 0x53], [0x7e, 0x25, 0x53], [0x00, 0x87, 0x51],
+    [0xab, 0x52, 0x36], [0x5f, 0x57, 0x4f], [0xc2, 0xc3, 0xc7], [0xff, 0xf1, 0xe8],
+    [0xff, 0x00, 0x4d], [0xff, 0xa3, 0x00], [0xff, 0xec, 0x27], [0x00, 0xe4, 0x36],
+    [0x29, 0xad, 0xff], [0x83, 0x76, 0x9c], [0xff, 0x77, 0xa8], [0xff, 0xcc, 0xaa]
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Dither {
+    None = 0,
+    Bayer = 1,
+    BlueNoise = 2
+}
+
+impl Dither {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Dither::None),
+            "bayer" => Some(Dither::Bayer),
+            "blue_noise" => Some(Dither::BlueNoise),
+            _ => None
+        }
+    }
+}
+
+// snaps every pixel to the closest palette color, after nudging it by an
+// ordered dither threshold so gradients turn into patterns instead of bands
+pub struct PaletteEffect {
+    parameters: EffectParameters,
+    pipeline: wgpu::RenderPipeline,
+    lookup_layout: wgpu::BindGroupLayout,
+    noise: texture::Texture,
+    lookup_bind_group: wgpu::BindGroup
+}
+
+impl PaletteEffect {
+    pub const NAME: &'static str = "palette";
+
+    // the blue noise tile is loaded from res/dither, any size works since
+    // the shader wraps it over the screen
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        input_layout: &wgpu::BindGroupLayout,
+        noise: texture::Texture
+    ) -> Self {
+        // colors is the palette length and only changes with set_palette
+        let parameters = EffectParameters::new(device, &[
+            ("dither", Dither::Bayer as u32 as f32),
+            ("dither_strength", 1.0),
+            ("colors", DEFAULT_PALETTE.len() as f32)
+        ], &["dither_strength"]);
+
+        let lookup_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: false }
+            },
+            count: None
+        };
+        // palette colors in a row followed by the blue noise tile
+        let lookup_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+            label: Some("Palette Lookup Bind Group Layout"),
+            entries: &[lookup_entry(0), lookup_entry(1)]
+        });
+        let pipeline = create_effect_pipeline(
+            device,
+            &[input_layout, &parameters.layout, &lookup_layout],
+            include_str!("../shaders/palette.wgsl"),
+            "Palette Effect Pipeline"
+        );
+
+        let lookup_bind_group = create_lookup_bind_group(device, queue, &lookup_layout, &noise, &DEFAULT_PALETTE);
+
+        Self {
+            parameters,
+            pipeline,
+            lookup_layout,
+            noise,
+            lookup_bind_group
+        }
+    }
+
+    pub fn set_palette(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, colors: &[[u8; 3]]) {
+        let colors = &colors[..colors.len().min(MAX_COLORS)];
+        self.lookup_bind_group = create_lookup_bind_group(device, queue, &self.lookup_layout, &self.noise, colors);
+        self.parameters.set("colors", colors.len() as f32);
+    }
+}
+
+impl Effect for PaletteEffect {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    // the palette file in "path" is loaded with the other resources and
+    // handed over through set_palette
+    fn configure(&mut self, settings: &Value) {
+        self.parameters.configure(settings);
+        if let Some(name) = settings["dither"].as_str() {
+            match Dither::from_name(name) {
+                Some(dither) => self.parameters.set("dither", dither as u32 as f32),
+                None => log::warn!("Unknown dither pattern: {}", name)
+            }
+        }
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        self.parameters.get(name)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        self.parameters.set(name, value);
+    }
+
+    fn apply(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView
+    ) {
+        self.parameters.upload(queue);
+        fullscreen_pass(
+            encoder,
+            "Palette Pass",
+            &self.pipeline,
+            &[input, &self.parameters.bind_group, &self.lookup_bind_group],
+            output
+        );
+    }
+}
+
+fn create_lookup_bind_group(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    noise: &texture::Texture,
+    colors: &[[u8; 3]]
+) -> wgpu::BindGroup {
+    let texels: Vec<u8> = colors.iter().flat_map(|[r, g, b]| [*r, *g, *b, 255]).collect();
+    // the texture is kept alive by the bind group
+    let palette = texture::Texture::from_rgba8(
+        device, queue, &texels, (colors.len() as u32, 1), false, Some("Palette Texture")
+    ).unwrap();
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Palette Lookup Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&palette.view)
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&noise.view)
+            }
+        ]
+    })
+}
@@ -0,0 +1,205 @@
+// the area and search textures smaa's blend weight pass looks up, built the
+// same way as AreaTex.py and SearchTex.py from the smaa sources. only the
+// orthogonal areas without a subsample offset are built, that's all smaa 1x
+// reads when diagonal detection is off
+
+// 5x5 slots of 16x16 distances, rg is how much the pixel and its neighbour
+// across the edge blend
+pub const AREA_SIZE: u32 = AREA_MAX_DISTANCE as u32 * 5;
+// left and right search deltas side by side, cropped to the rows that can
+// hold something other than zero and flipped like smaa's
+pub const SEARCH_SIZE: (u32, u32) = (64, 16);
+
+const AREA_MAX_DISTANCE: usize = 16;
+// u shapes shorter than this are rounded off
+const SMOOTH_MAX_DISTANCE: f32 = 32.0;
+
+// slot of every crossing edge pattern, the shader gets there with
+// round(4 * crossing edges) on each end of the line
+const PATTERN_SLOTS: [(usize, usize); 16] = [
+    (0, 0), (3, 0), (0, 3), (3, 3), (1, 0), (4, 0), (1, 3), (4, 3),
+    (0, 1), (3, 1), (0, 4), (3, 4), (1, 1), (4, 1), (1, 4), (4, 4)
+];
+
+// area under the line p1 -> p2 inside the pixel from x to x + 1, split into
+// the part below the edge (r) and above it (g)
+fn area(p1: (f32, f32), p2: (f32, f32), x: f32) -> [f32; 2] {
+    let d = (p2.0 - p1.0, p2.1 - p1.1);
+    let x1 = x;
+    let x2 = x + 1.0;
+    let y1 = p1.1 + d.1 * (x1 - p1.0) / d.0;
+    let y2 = p1.1 + d.1 * (x2 - p1.0) / d.0;
+
+    let inside = (x1 >= p1.0 && x1 < p2.0) || (x2 > p1.0 && x2 <= p2.0);
+    if !inside {
+        return [0.0, 0.0];
+    }
+    let trapezoid = 1f32.copysign(y1) == 1f32.copysign(y2) || y1.abs() < 1e-4 || y2.abs() < 1e-4;
+    if trapezoid {
+        let a = (y1 + y2) / 2.0;
+        return if a < 0.0 { [a.abs(), 0.0] } else { [0.0, a.abs()] };
+    }
+
+    // the line crosses the edge inside the pixel, two triangles
+    let x = -p1.1 * d.0 / d.1 + p1.0;
+    let a1 = if x > p1.0 { y1 * x.fract() / 2.0 } else { 0.0 };
+    let a2 = if x < p2.0 { y2 * (1.0 - x.fract()) / 2.0 } else { 0.0 };
+    let a = if a1.abs() > a2.abs() { a1 } else { -a2 };
+    if a < 0.0 {
+        [a1.abs(), a2.abs()]
+    } else {
+        [a2.abs(), a1.abs()]
+    }
+}
+
+// short u shapes would be blended too hard, their areas are eased towards
+// the square root
+fn smooth_area(d: f32, a1: [f32; 2], a2: [f32; 2]) -> [f32; 2] {
+    let p = (d / SMOOTH_MAX_DISTANCE).clamp(0.0, 1.0);
+    let smooth = |a: f32| {
+        let b = (a * 2.0).sqrt() * 0.5;
+        b + (a - b) * p
+    };
+    [smooth(a1[0]) + smooth(a2[0]), smooth(a1[1]) + smooth(a2[1])]
+}
+
+// area for the pixel `left` pixels into an edge `left + right + 1` long.
+// the pattern bits are the crossing edges below the left end, below the
+// right end, above the left end and above the right end
+fn area_ortho(pattern: usize, left: f32, right: f32) -> [f32; 2] {
+    let d = left + right + 1.0;
+    let above = 0.5;
+    let below = -0.5;
+    let middle = (d / 2.0, 0.0);
+
+    match pattern {
+        // l shapes only bend on the half next to their crossing edge, so
+        // they fade into the straight pattern 0 that is never filtered
+        1 if left <= right => area((0.0, below), middle, left),
+        2 if left >= right => area(middle, (d, below), left),
+        4 if left <= right => area((0.0, above), middle, left),
+        8 if left >= right => area(middle, (d, above), left),
+        3 => smooth_area(d, area((0.0, below), middle, left), area(middle, (d, below), left)),
+        12 => smooth_area(d, area((0.0, above), middle, left), area(middle, (d, above), left)),
+        // z shapes are one line from end to end
+        6 | 7 | 14 => area((0.0, above), (d, below), left),
+        9 | 11 | 13 => area((0.0, below), (d, above), left),
+        _ => [0.0, 0.0]
+    }
+}
+
+// rg pixels, pattern slots along both axes with the distances to the left
+// and right end squared so longer lines fit in the 16 texels
+pub fn area_texture() -> Vec<u8> {
+    let size = AREA_SIZE as usize;
+    let mut texels = vec![0; size * size * 4];
+    for (pattern, (slot_x, slot_y)) in PATTERN_SLOTS.iter().enumerate() {
+        for y in 0..AREA_MAX_DISTANCE {
+            for x in 0..AREA_MAX_DISTANCE {
+                let [r, g] = area_ortho(pattern, (x * x) as f32, (y * y) as f32);
+                let index = ((slot_y * AREA_MAX_DISTANCE + y) * size + slot_x * AREA_MAX_DISTANCE + x) * 4;
+                texels[index..index + 4].copy_from_slice(&[
+                    (r * 255.0).round() as u8,
+                    (g * 255.0).round() as u8,
+                    0,
+                    255
+                ]);
+            }
+        }
+    }
+    texels
+}
+
+// a bilinear fetch between four edges taken a quarter pixel left and an
+// eighth up of the last one, in 32nds so every combination stays distinct
+fn bilinear(edges: [u8; 4]) -> usize {
+    edges[0] as usize + edges[1] as usize * 3 + edges[2] as usize * 7 + edges[3] as usize * 21
+}
+
+// how many more pixels the edge runs on past the last fetch of a search to
+// the left, edges are [top left, top right, bottom left, bottom right]
+fn delta_left(left: [u8; 4], top: [u8; 4]) -> u8 {
+    let mut d = 0;
+    if top[3] == 1 {
+        d += 1;
+    }
+    if d == 1 && top[2] == 1 && left[1] != 1 && left[3] != 1 {
+        d += 1;
+    }
+    d
+}
+
+fn delta_right(left: [u8; 4], top: [u8; 4]) -> u8 {
+    let mut d = 0;
+    if top[3] == 1 && left[1] != 1 && left[3] != 1 {
+        d += 1;
+    }
+    if d == 1 && top[2] == 1 && left[0] != 1 && left[2] != 1 {
+        d += 1;
+    }
+    d
+}
+
+// single channel, 127 per pixel of delta. the column is the crossing edges
+// fetch (plus 33 for a search to the right) and the row 32 minus the fetch
+// of the edges being followed
+pub fn search_texture() -> Vec<u8> {
+    let (width, height) = (SEARCH_SIZE.0 as usize, SEARCH_SIZE.1 as usize);
+    let combinations = (0..16u8).map(|bits| [bits & 1, bits >> 1 & 1, bits >> 2 & 1, bits >> 3 & 1]);
+    let mut texels = vec![0; width * height];
+    for left in combinations.clone() {
+        for top in combinations.clone() {
+            let row = 32 - bilinear(top);
+            if row >= height {
+                continue;
+            }
+            let column = bilinear(left);
+            texels[row * width + column] = delta_left(left, top) * 127;
+            if column + 33 < width {
+                texels[row * width + column + 33] = delta_right(left, top) * 127;
+            }
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area_at(pattern: usize, x: usize, y: usize) -> [u8; 2] {
+        let (slot_x, slot_y) = PATTERN_SLOTS[pattern];
+        let size = AREA_SIZE as usize;
+        let index = ((slot_y * AREA_MAX_DISTANCE + y) * size + slot_x * AREA_MAX_DISTANCE + x) * 4;
+        let texels = area_texture();
+        [texels[index], texels[index + 1]]
+    }
+
+    #[test]
+    fn straight_and_crossed_edges_arent_blended() {
+        for pattern in [0, 5, 10, 15] {
+            for (x, y) in [(0, 0), (3, 5), (15, 15)] {
+                assert_eq!(area_at(pattern, x, y), [0, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn l_shapes_bend_towards_their_crossing_edge() {
+        // one pixel long, the line drops half a pixel over half its width
+        assert_eq!(area_at(1, 0, 0), [32, 0]);
+        assert_eq!(area_at(4, 0, 0), [0, 32]);
+        // mirrored patterns give the same area from the other end
+        assert_eq!(area_at(1, 2, 3), area_at(2, 3, 2));
+        // the half away from the crossing edge is left alone
+        assert_eq!(area_at(1, 3, 2), [0, 0]);
+    }
+
+    #[test]
+    fn search_texture_matches_smaa() {
+        // the first row of SearchTex.h
+        let texels = search_texture();
+        assert_eq!(texels[..12], [0xfe, 0xfe, 0, 0x7f, 0x7f, 0, 0, 0xfe, 0xfe, 0, 0x7f, 0x7f]);
+        assert_eq!(texels.len(), 64 * 16);
+    }
+}
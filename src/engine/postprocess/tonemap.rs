@@ -0,0 +1,96 @@
+use super::{Effect, ShaderEffect};
+use serde_json::Value;
+
+// operators understood by the tone mapping shader, the discriminant is
+// what gets written to the mode parameter
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ToneMapping {
+    Reinhard = 0,
+    Aces = 1,
+    Agx = 2
+}
+
+impl ToneMapping {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "reinhard" => Some(ToneMapping::Reinhard),
+            "aces" => Some(ToneMapping::Aces),
+            "agx" => Some(ToneMapping::Agx),
+            _ => None
+        }
+    }
+
+    pub fn from_mode(mode: f32) -> Self {
+        match mode as u32 {
+            0 => ToneMapping::Reinhard,
+            1 => ToneMapping::Aces,
+            _ => ToneMapping::Agx
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ToneMapping::Reinhard => ToneMapping::Aces,
+            ToneMapping::Aces => ToneMapping::Agx,
+            ToneMapping::Agx => ToneMapping::Reinhard
+        }
+    }
+}
+
+// a shader effect that also understands operator names in the settings.
+// this used to be part of the upscale pass, with the chain in place it's
+// its own stage instead so anti aliasing and the palette after it still
+// see display range colors, the upscale only ever resamples
+pub struct ToneMapEffect {
+    effect: ShaderEffect
+}
+
+impl ToneMapEffect {
+    pub const NAME: &'static str = "tonemap";
+
+    pub fn new(device: &wgpu::Device, input_layout: &wgpu::BindGroupLayout) -> Self {
+        let effect = ShaderEffect::new(
+            device,
+            input_layout,
+            Self::NAME,
+            include_str!("../shaders/tonemap.wgsl"),
+            &[("exposure", 1.0), ("mode", ToneMapping::Aces as u32 as f32)],
+            &["exposure"]
+        );
+        Self { effect }
+    }
+}
+
+impl Effect for ToneMapEffect {
+    fn name(&self) -> &str {
+        self.effect.name()
+    }
+
+    fn configure(&mut self, settings: &Value) {
+        self.effect.configure(settings);
+        if let Some(name) = settings["operator"].as_str() {
+            match ToneMapping::from_name(name) {
+                Some(tone_mapping) => self.effect.set_parameter("mode", tone_mapping as u32 as f32),
+                None => log::warn!("Unknown tone mapping operator: {}", name)
+            }
+        }
+    }
+
+    fn parameter(&self, name: &str) -> Option<f32> {
+        self.effect.parameter(name)
+    }
+
+    fn set_parameter(&mut self, name: &str, value: f32) {
+        self.effect.set_parameter(name, value);
+    }
+
+    fn apply(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::BindGroup,
+        output: &wgpu::TextureView
+    ) {
+        self.effect.apply(queue, encoder, input, output);
+    }
+}
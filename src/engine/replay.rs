@@ -0,0 +1,285 @@
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::input::{Action, Axis, InputEvent};
+
+// what run does with input, set from the command line:
+// --record <file> writes every frame's input and dt out on exit,
+// --replay <file> plays a recording back instead of live input,
+// --headless replays without a window and --capture <dir> saves each
+// replayed frame as a png
+#[derive(Debug, Default)]
+pub struct ReplayOptions {
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub headless: bool,
+    pub capture: Option<PathBuf>
+}
+
+impl ReplayOptions {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow::anyhow!("{} needs a path", arg));
+            match arg.as_str() {
+                "--record" => options.record = Some(value()?.into()),
+                "--replay" => options.replay = Some(value()?.into()),
+                "--capture" => options.capture = Some(value()?.into()),
+                "--headless" => options.headless = true,
+                _ => anyhow::bail!("Unknown argument: {}", arg)
+            }
+        }
+        if options.replay.is_none() && (options.headless || options.capture.is_some()) {
+            anyhow::bail!("--headless and --capture only work with --replay");
+        }
+        Ok(options)
+    }
+}
+
+// recorded after the input map so replays don't depend on the bindings,
+// resizes are kept for headless replays that have no window to follow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordedEvent {
+    Input(InputEvent),
+    Resize(u32, u32)
+}
+
+impl RecordedEvent {
+    fn from_json(event: &Value) -> anyhow::Result<Self> {
+        if let Some(name) = event["action"].as_str() {
+            let action = Action::from_name(name).ok_or_else(|| anyhow::anyhow!("Unknown action: {}", name))?;
+            return Ok(RecordedEvent::Input(InputEvent::Action(action, event["pressed"].as_bool().unwrap_or(false))));
+        }
+        if let Some(name) = event["axis"].as_str() {
+            let axis = Axis::from_name(name).ok_or_else(|| anyhow::anyhow!("Unknown axis: {}", name))?;
+            return Ok(RecordedEvent::Input(InputEvent::Axis(axis, event["value"].as_f64().unwrap_or(0.0) as f32)));
+        }
+        if let Some(size) = event["resize"].as_array() {
+            let dimension = |i: usize| size.get(i).and_then(Value::as_u64).unwrap_or(1) as u32;
+            return Ok(RecordedEvent::Resize(dimension(0), dimension(1)));
+        }
+        anyhow::bail!("Unknown recorded event: {}", event)
+    }
+
+    fn to_json(self) -> Value {
+        match self {
+            RecordedEvent::Input(InputEvent::Action(action, pressed)) => json!({ "action": action.name(), "pressed": pressed }),
+            RecordedEvent::Input(InputEvent::Axis(axis, value)) => json!({ "axis": axis.name(), "value": value }),
+            RecordedEvent::Resize(width, height) => json!({ "resize": [width, height] })
+        }
+    }
+}
+
+// the events that arrived before a frame, then the dt it was updated with
+#[derive(Debug, Clone)]
+pub struct RecordedFrame {
+    pub events: Vec<RecordedEvent>,
+    pub dt: Duration
+}
+
+#[derive(Debug, Clone)]
+pub struct InputRecording {
+    // the window size when recording started
+    pub size: (u32, u32),
+    pub frames: Vec<RecordedFrame>
+}
+
+impl InputRecording {
+    pub fn load(file: &Path) -> anyhow::Result<Self> {
+        let json: Value = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+        let size = json["size"].as_array().ok_or_else(|| anyhow::anyhow!("Recording without a size"))?;
+        let dimension = |i: usize| size.get(i).and_then(Value::as_u64).unwrap_or(1) as u32;
+        let frames = json["frames"].as_array().into_iter().flatten()
+            .map(|frame| {
+                let events = frame["events"].as_array().into_iter().flatten()
+                    .map(RecordedEvent::from_json)
+                    .collect::<anyhow::Result<_>>()?;
+                // nanoseconds so the replayed dt is exactly the recorded one
+                let dt = Duration::from_nanos(frame["dt"].as_u64().unwrap_or(0));
+                Ok(RecordedFrame { events, dt })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            size: (dimension(0), dimension(1)),
+            frames
+        })
+    }
+
+    pub fn save(&self, file: &Path) -> anyhow::Result<()> {
+        let json = json!({
+            "size": [self.size.0, self.size.1],
+            "frames": self.frames.iter().map(|frame| json!({
+                "dt": frame.dt.as_nanos() as u64,
+                "events": frame.events.iter().map(|event| event.to_json()).collect::<Vec<_>>()
+            })).collect::<Vec<_>>()
+        });
+        if let Some(directory) = file.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(file, serde_json::to_string(&json)?)?;
+        Ok(())
+    }
+}
+
+pub struct InputRecorder {
+    recording: InputRecording,
+    pending: Vec<RecordedEvent>
+}
+
+impl InputRecorder {
+    pub fn new(size: (u32, u32)) -> Self {
+        Self {
+            recording: InputRecording { size, frames: Vec::new() },
+            pending: Vec::new()
+        }
+    }
+
+    pub fn record(&mut self, event: RecordedEvent) {
+        self.pending.push(event);
+    }
+
+    // events after the last frame never affected anything and are dropped
+    pub fn end_frame(&mut self, dt: Duration) {
+        let events = std::mem::take(&mut self.pending);
+        self.recording.frames.push(RecordedFrame { events, dt });
+    }
+
+    pub fn finish(self) -> InputRecording {
+        self.recording
+    }
+}
+
+pub struct InputReplay {
+    frames: std::vec::IntoIter<RecordedFrame>
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Self {
+        Self {
+            frames: recording.frames.into_iter()
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        self.frames.next()
+    }
+}
+
+// copies a frame back from the gpu, rows are padded to the copy alignment
+pub fn save_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, file: &Path) -> anyhow::Result<()> {
+    let (width, height) = (texture.width(), texture.height());
+    let bytes_per_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Capture Buffer"),
+        size: (bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Capture Encoder")
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(height)
+            }
+        },
+        texture.size()
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = slice.get_mapped_range();
+    let mut image = image::RgbaImage::new(width, height);
+    for (y, row) in data.chunks(bytes_per_row as usize).enumerate() {
+        for x in 0..width {
+            let i = x as usize * 4;
+            image.put_pixel(x, y as u32, image::Rgba([row[i], row[i + 1], row[i + 2], 255]));
+        }
+    }
+    drop(data);
+    buffer.unmap();
+
+    image.save(file)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn events_round_trip_through_json() {
+        let events = [
+            RecordedEvent::Input(InputEvent::Action(Action::MoveForward, true)),
+            RecordedEvent::Input(InputEvent::Action(Action::Sprint, false)),
+            RecordedEvent::Input(InputEvent::Axis(Axis::LookX, -12.5)),
+            RecordedEvent::Resize(1280, 720)
+        ];
+        for event in events {
+            assert_eq!(RecordedEvent::from_json(&event.to_json()).unwrap(), event);
+        }
+        assert!(RecordedEvent::from_json(&json!({ "action": "not_an_action" })).is_err());
+        assert!(RecordedEvent::from_json(&json!({})).is_err());
+    }
+
+    #[test]
+    fn recording_round_trips_through_a_file() {
+        let mut recorder = InputRecorder::new((640, 480));
+        recorder.record(RecordedEvent::Input(InputEvent::Action(Action::MoveLeft, true)));
+        recorder.end_frame(Duration::from_nanos(16_666_667));
+        recorder.end_frame(Duration::from_millis(7));
+        recorder.record(RecordedEvent::Resize(800, 600));
+        recorder.record(RecordedEvent::Input(InputEvent::Axis(Axis::Zoom, 1.0)));
+        recorder.end_frame(Duration::from_nanos(1));
+        // never reaches a frame
+        recorder.record(RecordedEvent::Input(InputEvent::Action(Action::MoveLeft, false)));
+        let recording = recorder.finish();
+
+        let file = std::env::temp_dir().join(format!("engi-replay-test-{}.json", std::process::id()));
+        recording.save(&file).unwrap();
+        let loaded = InputRecording::load(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(loaded.size, (640, 480));
+        assert_eq!(loaded.frames.len(), 3);
+        for (loaded, recorded) in loaded.frames.iter().zip(&recording.frames) {
+            assert_eq!(loaded.dt, recorded.dt);
+            assert_eq!(loaded.events, recorded.events);
+        }
+    }
+
+    #[test]
+    fn options_parse_paths_and_flags() {
+        let options = ReplayOptions::from_args(args(&["--replay", "a.json", "--headless", "--capture", "frames"])).unwrap();
+        assert_eq!(options.replay, Some(PathBuf::from("a.json")));
+        assert_eq!(options.capture, Some(PathBuf::from("frames")));
+        assert!(options.headless);
+        assert!(options.record.is_none());
+
+        let options = ReplayOptions::from_args(args(&[])).unwrap();
+        assert!(options.record.is_none() && options.replay.is_none() && !options.headless);
+    }
+
+    #[test]
+    fn options_reject_headless_or_capture_without_replay() {
+        assert!(ReplayOptions::from_args(args(&["--headless"])).is_err());
+        assert!(ReplayOptions::from_args(args(&["--record", "a.json", "--headless"])).is_err());
+        assert!(ReplayOptions::from_args(args(&["--capture", "frames"])).is_err());
+        assert!(ReplayOptions::from_args(args(&["--replay"])).is_err());
+        assert!(ReplayOptions::from_args(args(&["--fast"])).is_err());
+    }
+}
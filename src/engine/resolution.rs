@@ -1,45 +1,127 @@
 use super::{
-    textures::texture,
-    pipeline
+    pipeline,
+    postprocess,
+    textures::texture
 };
+use serde_json::Value;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// how the internal image is put on the window
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UpscaleMode {
+    // covers the whole window, pixels stop being square when the aspect differs
+    Stretch,
+    // largest size with the internal aspect, bars fill the rest
+    Letterbox,
+    // largest whole multiple of the internal size, so every pixel gets the same size
+    Integer,
+    // the internal width follows the window aspect at the configured height
+    MatchAspect
+}
+
+impl UpscaleMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "stretch" => Some(UpscaleMode::Stretch),
+            "letterbox" => Some(UpscaleMode::Letterbox),
+            "integer" => Some(UpscaleMode::Integer),
+            "match_aspect" => Some(UpscaleMode::MatchAspect),
+            _ => None
+        }
+    }
+
+    // the internal resolution to render at before any dynamic scaling
+    pub fn internal_resolution(self, base: (u32, u32), surface: (u32, u32)) -> (u32, u32) {
+        match self {
+            UpscaleMode::MatchAspect => {
+                let width = (base.1 as f32 * surface.0 as f32 / surface.1 as f32).round() as u32;
+                (width.max(1), base.1)
+            }
+            _ => base
+        }
+    }
+}
+
+// how the internal image is resampled to the upscaled size, the
+// discriminant is what gets written to the filter_mode parameter
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    Nearest = 0,
+    Bilinear = 1,
+    // nearest inside each pixel with a one pixel blend at its border, keeps
+    // pixel art crisp at non integer scales without uneven pixel sizes
+    SharpBilinear = 2,
+    // fsr 1 style edge adaptive upsampling followed by sharpening
+    Fsr = 3
+}
+
+impl UpscaleFilter {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nearest" => Some(UpscaleFilter::Nearest),
+            "bilinear" => Some(UpscaleFilter::Bilinear),
+            "sharp_bilinear" => Some(UpscaleFilter::SharpBilinear),
+            "fsr" => Some(UpscaleFilter::Fsr),
+            _ => None
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            UpscaleFilter::Nearest => UpscaleFilter::Bilinear,
+            UpscaleFilter::Bilinear => UpscaleFilter::SharpBilinear,
+            UpscaleFilter::SharpBilinear => UpscaleFilter::Fsr,
+            UpscaleFilter::Fsr => UpscaleFilter::Nearest
+        }
+    }
+}
+
+// the easu output at the viewport size, read by the sharpening pass
+struct FsrTarget {
+    texture: texture::Texture,
+    bind_group: wgpu::BindGroup
+}
 
 pub struct ResolutionScalingPipeline {
     pipeline: wgpu::RenderPipeline,
-    bind_group: wgpu::BindGroup,
-    texture: texture::Texture
+    easu_pipeline: wgpu::RenderPipeline,
+    rcas_pipeline: wgpu::RenderPipeline,
+    parameters: postprocess::EffectParameters,
+    filter: UpscaleFilter,
+    fsr_target: Option<FsrTarget>,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    sample_count: u32,
+    mode: UpscaleMode,
+    resolution: (u32, u32),
+    surface_size: (u32, u32),
+    // the scene is drawn here with msaa on and resolved into the chain
+    multisampled_view: Option<wgpu::TextureView>,
+    // one per chain target, the last effect decides which one is upscaled
+    bind_groups: [wgpu::BindGroup; 3],
+    pub post_process: postprocess::PostProcessChain
 }
 
 impl ResolutionScalingPipeline {
     pub fn new(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
-        resolution: (u32, u32)
+        resolution: (u32, u32),
+        sample_count: u32,
+        mode: UpscaleMode
     ) -> Self {
-        let texture = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("Resolution Scaling Pipeline Texture"),
-            size: wgpu::Extent3d {
-                width: resolution.0,
-                height: resolution.1,
-                depth_or_array_layers: 1
-            },
-            dimension: wgpu::TextureDimension::D2,
-            format: config.format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            sample_count: 1,
-            mip_level_count: 1,
-            view_formats: &[]
-        });
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let post_process = postprocess::PostProcessChain::new(device, resolution);
+        let multisampled_view = create_multisampled_view(device, resolution, sample_count);
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Nearest,
             ..Default::default()
         });
-        let pipeline_texture = texture::Texture { texture, view, sampler };
 
         let layout = device.create_bind_group_layout(
             &wgpu::BindGroupLayoutDescriptor {
@@ -63,60 +145,210 @@ impl ResolutionScalingPipeline {
                 }
             ]
         });
-        let bind_group = device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-            label: Some("Resolution Scaling Pipeline Bind Group"),
-            layout: &layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&pipeline_texture.view)
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&pipeline_texture.sampler)
-                }
-            ]
-        });
+        let bind_groups = create_bind_groups(device, &layout, &sampler, &post_process);
+
+        // sizes are filled in by update_parameters, sharpness is in stops
+        // with 0 the strongest and only used by fsr
+        let parameters = postprocess::EffectParameters::new(device, &[
+            ("filter_mode", UpscaleFilter::Nearest as u32 as f32),
+            ("sharpness", 0.2),
+            ("source_width", resolution.0 as f32),
+            ("source_height", resolution.1 as f32),
+            ("output_width", config.width as f32),
+            ("output_height", config.height as f32),
+            ("output_x", 0.0),
+            ("output_y", 0.0)
+        ], &["sharpness"]);
 
-        let shader = wgpu::include_wgsl!("shaders/resolution_scale.wgsl");
         let pipeline_layout = device.create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
             label: Some("Resolution Scaling Pipeline Layout"),
-            bind_group_layouts: &[&layout],
+            bind_group_layouts: &[&layout, &parameters.layout],
             push_constant_ranges: &[]
         });
-        let pipeline = pipeline::create_render_pipeline(
-            device,
-            &pipeline_layout,
-            config.format,
-            None,
-            &[],
-            shader,
-            "Resolution Scaling Pipeline",
-            false
+        let create_pipeline = |source: &str, format, label| {
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(format!(
+                    "{}\n{}\n{}",
+                    include_str!("shaders/fullscreen.wgsl"),
+                    include_str!("shaders/upscale_common.wgsl"),
+                    source
+                ).into())
+            };
+            pipeline::create_render_pipeline(
+                device, &pipeline_layout, format, None, &[], shader, label, false, pipeline::DepthMode::Standard, 1
+            )
+        };
+        let pipeline = create_pipeline(
+            include_str!("shaders/resolution_scale.wgsl"), config.format, "Resolution Scaling Pipeline"
+        );
+        let easu_pipeline = create_pipeline(
+            include_str!("shaders/easu.wgsl"), texture::Texture::HDR_FORMAT, "Easu Pipeline"
         );
-        
-        Self {
+        let rcas_pipeline = create_pipeline(include_str!("shaders/rcas.wgsl"), config.format, "Rcas Pipeline");
+
+        let mut scaling = Self {
             pipeline,
-            bind_group,
-            texture: pipeline_texture,
+            easu_pipeline,
+            rcas_pipeline,
+            parameters,
+            filter: UpscaleFilter::Nearest,
+            fsr_target: None,
+            layout,
+            sampler,
+            sample_count,
+            mode,
+            resolution,
+            surface_size: (config.width, config.height),
+            multisampled_view,
+            bind_groups,
+            post_process
+        };
+        scaling.update_parameters(device);
+        scaling
+    }
+
+    // the resolution block of the settings file
+    pub fn configure(&mut self, device: &wgpu::Device, settings: &Value) {
+        self.parameters.configure(settings);
+        if let Some(name) = settings["filter"].as_str() {
+            match UpscaleFilter::from_name(name) {
+                Some(filter) => self.set_filter(device, filter),
+                None => log::warn!("Unknown upscale filter: {}", name)
+            }
+        }
+    }
+
+    pub fn filter(&self) -> UpscaleFilter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, device: &wgpu::Device, filter: UpscaleFilter) {
+        self.filter = filter;
+        self.parameters.set("filter_mode", filter as u32 as f32);
+        self.update_parameters(device);
+    }
+
+    // keeps the sizes the filters work with and the fsr target in sync
+    // with the viewport
+    fn update_parameters(&mut self, device: &wgpu::Device) {
+        let (x, y, width, height) = self.viewport();
+        self.parameters.set("source_width", self.resolution.0 as f32);
+        self.parameters.set("source_height", self.resolution.1 as f32);
+        self.parameters.set("output_width", width);
+        self.parameters.set("output_height", height);
+        self.parameters.set("output_x", x);
+        self.parameters.set("output_y", y);
+
+        self.fsr_target = (self.filter == UpscaleFilter::Fsr).then(|| {
+            let size = ((width as u32).max(1), (height as u32).max(1));
+            let texture = postprocess::create_target(device, size, "Easu Target");
+            let bind_group = create_bind_group(device, &self.layout, &self.sampler, &texture.view);
+            FsrTarget { texture, bind_group }
+        });
+    }
+
+    pub fn mode(&self) -> UpscaleMode {
+        self.mode
+    }
+
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
+    pub fn set_surface_size(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        self.surface_size = size;
+        self.update_parameters(device);
+    }
+
+    // the size the projection aspect has to come from, when the image is
+    // stretched over the window the scene has to be squashed to match
+    pub fn projection_size(&self) -> (u32, u32) {
+        match self.mode {
+            UpscaleMode::Stretch | UpscaleMode::MatchAspect => self.surface_size,
+            UpscaleMode::Letterbox | UpscaleMode::Integer => self.resolution
         }
     }
 
+    // x, y, width and height of the upscaled image on the surface
+    fn viewport(&self) -> (f32, f32, f32, f32) {
+        let (surface_width, surface_height) = (self.surface_size.0 as f32, self.surface_size.1 as f32);
+        let (width, height) = (self.resolution.0 as f32, self.resolution.1 as f32);
+        let fit = (surface_width / width).min(surface_height / height);
+        let scale = match self.mode {
+            UpscaleMode::Stretch | UpscaleMode::MatchAspect => return (0.0, 0.0, surface_width, surface_height),
+            UpscaleMode::Letterbox => fit,
+            // windows smaller than the internal image fall back to fitting it
+            UpscaleMode::Integer if fit >= 1.0 => fit.floor(),
+            UpscaleMode::Integer => fit
+        };
+        // whole pixel offsets and sizes keep integer scaling pixel perfect
+        let (width, height) = ((width * scale).round(), (height * scale).round());
+        (
+            ((surface_width - width) * 0.5).floor(),
+            ((surface_height - height) * 0.5).floor(),
+            width,
+            height
+        )
+    }
+
+    // changes the internal resolution, anything else drawn at it (depth,
+    // ssao) has to be recreated alongside
+    pub fn resize(&mut self, device: &wgpu::Device, resolution: (u32, u32)) {
+        self.resolution = resolution;
+        self.post_process.resize(device, resolution);
+        self.multisampled_view = create_multisampled_view(device, resolution, self.sample_count);
+        self.bind_groups = create_bind_groups(device, &self.layout, &self.sampler, &self.post_process);
+        self.update_parameters(device);
+    }
+
+    // where the scene is rendered
     pub fn view(&self) -> &wgpu::TextureView {
-        &self.texture.view
+        self.multisampled_view.as_ref().unwrap_or(self.post_process.scene_view())
+    }
+
+    // set as the resolve target of the scene pass when it is multisampled
+    pub fn resolve_target(&self) -> Option<&wgpu::TextureView> {
+        self.multisampled_view.as_ref().map(|_| self.post_process.scene_view())
     }
 
-    pub fn upscale(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+    // post processing runs at the internal resolution, the upscale to the
+    // surface is always the last stage
+    pub fn upscale(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView
+    ) {
+        let final_target = self.post_process.run(queue, encoder);
+        self.parameters.upload(queue);
+
+        // fsr upsamples into its own target first and sharpens that onto
+        // the surface, the other filters sample the chain directly
+        let (pipeline, input) = match self.fsr_target.as_ref() {
+            Some(target) => {
+                postprocess::fullscreen_pass(
+                    encoder,
+                    "Easu Pass",
+                    &self.easu_pipeline,
+                    &[&self.bind_groups[final_target], &self.parameters.bind_group],
+                    &target.texture.view
+                );
+                (&self.rcas_pipeline, &target.bind_group)
+            }
+            None => (&self.pipeline, &self.bind_groups[final_target])
+        };
+
         let mut render_pass = encoder.begin_render_pass(
             &wgpu::RenderPassDescriptor {
             label: Some("Resolution Scaling Render Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &output,
+                view: output,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
+                    // the letterbox bars
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                     store: wgpu::StoreOp::Store
                 }
             })],
@@ -124,8 +356,250 @@ impl ResolutionScalingPipeline {
             occlusion_query_set: None,
             timestamp_writes: None
         });
-        render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        let (x, y, width, height) = self.viewport();
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, input, &[]);
+        render_pass.set_bind_group(1, &self.parameters.bind_group, &[]);
         render_pass.draw(0..3, 0..1);
     }
-}
\ No newline at end of file
+}
+
+fn create_multisampled_view(
+    device: &wgpu::Device,
+    resolution: (u32, u32),
+    sample_count: u32
+) -> Option<wgpu::TextureView> {
+    (sample_count > 1).then(|| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Multisampled Scene Texture"),
+            size: wgpu::Extent3d {
+                width: resolution.0,
+                height: resolution.1,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture::Texture::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[]
+        }).create_view(&wgpu::TextureViewDescriptor::default())
+    })
+}
+
+fn create_bind_groups(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    post_process: &postprocess::PostProcessChain
+) -> [wgpu::BindGroup; 3] {
+    post_process.target_views().map(|view| create_bind_group(device, layout, sampler, view))
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    view: &wgpu::TextureView
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Resolution Scaling Pipeline Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view)
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler)
+            }
+        ]
+    })
+}
+
+// the scale only moves in steps of this, and then waits for the frame time
+// to settle before moving again
+const SCALE_STEP: f32 = 0.05;
+const COOLDOWN_FRAMES: u32 = 30;
+const SMOOTHING: f32 = 0.1;
+
+// picks an internal resolution between min and max scale of the base one
+// to keep the frame time around a target, renders cost roughly the pixel
+// count so the scale goes down with the square root of the overshoot.
+// the frame time comes from a FrameTimer, the frame dt sits at the vsync
+// interval whenever the render is faster than that and could never go up
+pub struct DynamicResolution {
+    base: (u32, u32),
+    scale: f32,
+    min_scale: f32,
+    max_scale: f32,
+    target_frame_time: f32,
+    average_frame_time: f32,
+    cooldown: u32
+}
+
+impl DynamicResolution {
+    // the dynamic block of the resolution settings, None unless enabled
+    pub fn new(base: (u32, u32), settings: &Value) -> Option<Self> {
+        if !settings["enabled"].as_bool().unwrap_or(false) {
+            return None;
+        }
+        let value = |name, default| settings[name].as_f64().map_or(default, |value| value as f32);
+        // milliseconds in the settings file
+        let target_frame_time = value("target_frame_time", 1000.0 / 60.0) / 1000.0;
+        let min_scale = value("min_scale", 0.5).max(SCALE_STEP);
+        let max_scale = value("max_scale", 1.0).max(min_scale);
+
+        Some(Self {
+            base,
+            scale: 1.0f32.clamp(min_scale, max_scale),
+            min_scale,
+            max_scale,
+            target_frame_time,
+            average_frame_time: target_frame_time,
+            cooldown: COOLDOWN_FRAMES
+        })
+    }
+
+    // the window changed the base resolution, returns the scaled one
+    pub fn set_base(&mut self, base: (u32, u32)) -> (u32, u32) {
+        self.base = base;
+        self.resolution()
+    }
+
+    pub fn resolution(&self) -> (u32, u32) {
+        (
+            ((self.base.0 as f32 * self.scale).round() as u32).max(1),
+            ((self.base.1 as f32 * self.scale).round() as u32).max(1)
+        )
+    }
+
+    // feeds the last frame time, returns the new resolution when it changed
+    pub fn update(&mut self, frame_time: Duration) -> Option<(u32, u32)> {
+        // a single hitch like a window drag shouldn't halve the resolution
+        let frame_time = frame_time.as_secs_f32().min(self.target_frame_time * 2.0);
+        self.average_frame_time += (frame_time - self.average_frame_time) * SMOOTHING;
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return None;
+        }
+
+        // some headroom before going back up, otherwise it keeps bouncing
+        // between two sizes right at the target
+        let ratio = self.average_frame_time / self.target_frame_time;
+        let scale = if ratio > 1.05 {
+            let wanted = self.scale / ratio.sqrt();
+            ((wanted / SCALE_STEP).floor() * SCALE_STEP).min(self.scale - SCALE_STEP)
+        } else if ratio < 0.85 {
+            self.scale + SCALE_STEP
+        } else {
+            return None;
+        };
+        let scale = scale.clamp(self.min_scale, self.max_scale);
+        if (scale - self.scale).abs() < SCALE_STEP * 0.5 {
+            return None;
+        }
+
+        let previous = self.resolution();
+        self.scale = scale;
+        self.cooldown = COOLDOWN_FRAMES;
+        let resolution = self.resolution();
+        (resolution != previous).then_some(resolution)
+    }
+}
+
+// how long the gpu spent on a frame, read back a frame or two late. needs
+// timestamp queries, the cpu side time barely moves with the resolution
+// and vsync holds the frame to frame time at the refresh interval
+pub struct FrameTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    // nanoseconds per timestamp tick
+    period: f32,
+    writing: bool,
+    // only one readback at a time, frames in between aren't timed
+    pending: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>
+}
+
+impl FrameTimer {
+    // the device gets these when the adapter has them
+    pub const FEATURES: wgpu::Features = wgpu::Features::TIMESTAMP_QUERY
+        .union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS);
+
+    // None when the device has no timestamp queries
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(Self::FEATURES) {
+            return None;
+        }
+        let buffer = |label, usage| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: 2 * wgpu::QUERY_SIZE as u64,
+            usage,
+            mapped_at_creation: false
+        });
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Frame Timer Query Set"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2
+            }),
+            resolve_buffer: buffer(
+                "Frame Timer Resolve Buffer", wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC
+            ),
+            readback_buffer: buffer(
+                "Frame Timer Readback Buffer", wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST
+            ),
+            period: queue.get_timestamp_period(),
+            writing: false,
+            pending: None
+        })
+    }
+
+    // around everything recorded for the frame
+    pub fn begin(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.pending.is_none() {
+            encoder.write_timestamp(&self.query_set, 0);
+            self.writing = true;
+        }
+    }
+
+    pub fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if self.writing {
+            encoder.write_timestamp(&self.query_set, 1);
+            encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &self.resolve_buffer, 0, &self.readback_buffer, 0, self.resolve_buffer.size()
+            );
+        }
+    }
+
+    // after the encoder from begin and end has been submitted
+    pub fn submitted(&mut self) {
+        if self.writing {
+            self.writing = false;
+            let (sender, receiver) = mpsc::channel();
+            self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                sender.send(result).ok();
+            });
+            self.pending = Some(receiver);
+        }
+    }
+
+    // the latest frame time once, None until a new one has arrived
+    pub fn take(&mut self, device: &wgpu::Device) -> Option<Duration> {
+        device.poll(wgpu::Maintain::Poll);
+        let result = self.pending.as_ref()?.try_recv().ok()?;
+        self.pending = None;
+        result.ok()?;
+
+        let data = self.readback_buffer.slice(..).get_mapped_range();
+        let ticks: &[u64] = bytemuck::cast_slice(&data);
+        let nanoseconds = ticks[1].saturating_sub(ticks[0]) as f64 * self.period as f64;
+        drop(data);
+        self.readback_buffer.unmap();
+        Some(Duration::from_nanos(nanoseconds as u64))
+    }
+}
@@ -1,22 +1,24 @@
 use std::io::{BufReader, Cursor};
 use wgpu::util::DeviceExt;
 
-use super::{model, textures, ecs};
+use super::{model, textures, ecs, uniform, settings, camera_path, input};
 
-pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
+// the copy of res build.rs makes next to the build, everything is loaded
+// from and saved to it at runtime
+fn res_path(file_name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("OUT_DIR"))
         .join("res")
-        .join(file_name);
-    let txt = std::fs::read_to_string(path)?;
+        .join(file_name)
+}
+
+pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
+    let txt = std::fs::read_to_string(res_path(file_name))?;
 
     Ok(txt)
 }
 
 pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
-        .join("res")
-        .join(file_name);
-    let data = std::fs::read(path)?;
+    let data = std::fs::read(res_path(file_name))?;
 
     Ok(data)
 }
@@ -30,6 +32,18 @@ pub async fn load_texture(
     textures::texture::Texture::from_bytes(device, queue,&data, file_name)
 }
 
+// for maps holding data rather than colors, sampled without gamma decoding
+pub async fn load_linear_texture(
+    file_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue
+) -> anyhow::Result<textures::texture::Texture> {
+    let data = load_binary(file_name).await?;
+    let image = image::load_from_memory(&data)?;
+    let dimensions = image::GenericImageView::dimensions(&image);
+    textures::texture::Texture::from_rgba8(device, queue, &image.to_rgba8(), dimensions, false, Some(file_name))
+}
+
 pub async fn load_model(
     file_name: &str,
     device: &wgpu::Device
@@ -80,6 +94,7 @@ pub async fn load_model(
                 }
             }
         }).collect::<Vec<_>>();
+        let bounds = model::Aabb::from_vertices(&vertices);
 
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("{:?} Vertex Buffer", file_name)),
@@ -96,7 +111,10 @@ pub async fn load_model(
             _name: file_name.to_string(),
             vertex_buffer,
             index_buffer,
-            num_elements: m.mesh.indices.len() as u32
+            num_elements: m.mesh.indices.len() as u32,
+            material: 0,
+            bounds,
+            sphere: model::BoundingSphere::from_vertices(&vertices, &bounds)
         }
     }).collect::<Vec<_>>();
 
@@ -107,9 +125,25 @@ pub async fn load_material (
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
-    file_name: &str
+    file_name: &str,
+    specular_file_name: Option<&str>,
+    specular_color: cgmath::Vector3<f32>,
+    shininess: f32
 ) -> anyhow::Result<textures::texture::Material> {
     let diffuse = load_texture(file_name, device, queue).await?;
+    // without a specular map the specular color is applied uniformly
+    let specular = match specular_file_name {
+        Some(specular_file_name) => load_linear_texture(specular_file_name, device, queue).await?,
+        None => textures::texture::Texture::from_color(
+            device, queue, [255, 255, 255, 255], false, "Default Specular Texture"
+        )?
+    };
+    let material_uniform = uniform::MaterialUniform::new(specular_color, shininess);
+    let material_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(&format!("{:?} Material Buffer", file_name)),
+        contents: bytemuck::cast_slice(&[material_uniform]),
+        usage: wgpu::BufferUsages::UNIFORM
+    });
     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
         layout,
@@ -121,6 +155,18 @@ pub async fn load_material (
             wgpu::BindGroupEntry {
                 binding: 1,
                 resource: wgpu::BindingResource::Sampler(&diffuse.sampler)
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&specular.view)
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&specular.sampler)
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: material_buffer.as_entire_binding()
             }
         ]
     });
@@ -132,10 +178,105 @@ pub async fn load_scene(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     texture_layout: &wgpu::BindGroupLayout,
-    cubemap_layout: &wgpu::BindGroupLayout
-) -> anyhow::Result<ecs::ecs::World> {
+    cubemap_layout: &wgpu::BindGroupLayout,
+    pbr_layout: &wgpu::BindGroupLayout,
+    camera_layout: &wgpu::BindGroupLayout
+) -> anyhow::Result<ecs::scene::Scene> {
+    let json = load_string(file_name).await?;
+    Ok(ecs::scene::parse_scene(&json, device, queue, texture_layout, cubemap_layout, pbr_layout, camera_layout).await)
+}
+
+pub async fn load_camera_path(file_name: &str) -> anyhow::Result<camera_path::CameraPath> {
     let json = load_string(file_name).await?;
-    Ok(ecs::scene::parse_scene(&json, device, queue, texture_layout, cubemap_layout).await)
+    Ok(camera_path::CameraPath::from_json(&serde_json::from_str(&json)?))
+}
+
+pub async fn load_input_map(file_name: &str) -> anyhow::Result<input::InputMap> {
+    let json = load_string(file_name).await?;
+    Ok(input::InputMap::from_json(&serde_json::from_str(&json)?))
+}
+
+// written next to the files they're loaded from so they work without a
+// rebuild. a build that copies res again overwrites files of the same name
+fn save_json(file_name: &str, value: &serde_json::Value) -> anyhow::Result<std::path::PathBuf> {
+    let file = res_path(file_name);
+    if let Some(directory) = file.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    std::fs::write(&file, serde_json::to_string_pretty(value)?)?;
+
+    Ok(file)
+}
+
+// scenes can point at recordings by their file name under paths/
+pub fn save_camera_path(path: &camera_path::CameraPath) -> anyhow::Result<std::path::PathBuf> {
+    save_json(&format!("paths/{}.json", file_stem(&path.name)), &path.to_json())
+}
+
+pub fn save_input_map(map: &input::InputMap) -> anyhow::Result<std::path::PathBuf> {
+    save_json("input.json", &map.to_json())
+}
+
+// names come from scene files, anything that could leave the folder or
+// isn't allowed in a file name becomes an underscore
+fn file_stem(name: &str) -> String {
+    let stem: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if stem.is_empty() {
+        "path".to_string()
+    } else {
+        stem
+    }
+}
+
+pub async fn load_settings(file_name: &str) -> anyhow::Result<settings::Settings> {
+    let json = load_string(file_name).await?;
+    settings::parse_settings(&json)
+}
+
+// palette colors in srgb, from a png strip (every distinct pixel in
+// reading order), a .hex file or a GIMP .gpl file
+pub async fn load_palette(file_name: &str) -> anyhow::Result<Vec<[u8; 3]>> {
+    let mut colors: Vec<[u8; 3]> = Vec::new();
+    if file_name.ends_with(".hex") {
+        let text = load_string(file_name).await?;
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let hex = line.trim_start_matches('#');
+            let value = u32::from_str_radix(hex, 16)
+                .map_err(|e| anyhow::anyhow!("Invalid color {} in {}: {}", line, file_name, e))?;
+            colors.push([(value >> 16) as u8, (value >> 8) as u8, value as u8]);
+        }
+    } else if file_name.ends_with(".gpl") {
+        let text = load_string(file_name).await?;
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with("GIMP Palette")
+                || line.starts_with("Name:") || line.starts_with("Columns:") {
+                continue;
+            }
+            let channels: Vec<u8> = line.split_whitespace()
+                .take(3)
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|e| anyhow::anyhow!("Invalid color {} in {}: {}", line, file_name, e))?;
+            if let [r, g, b] = channels[..] {
+                colors.push([r, g, b]);
+            }
+        }
+    } else {
+        let data = load_binary(file_name).await?;
+        let image = image::load_from_memory(&data)?.to_rgb8();
+        for pixel in image.pixels() {
+            if !colors.contains(&pixel.0) {
+                colors.push(pixel.0);
+            }
+        }
+    }
+
+    if colors.is_empty() {
+        anyhow::bail!("Palette {} has no colors", file_name);
+    }
+    Ok(colors)
 }
 
 pub async fn load_cubemap_files(
@@ -150,6 +291,25 @@ pub async fn load_cubemap_files(
         binaries.push(binary_data);
     }
     let cubemap = textures::cubemap::Cubemap::from_bytes(binaries, device, queue)?;
+    Ok(create_skybox(cubemap, device, layout))
+}
+
+pub async fn load_hdr_cubemap(
+    file_name: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout
+) -> anyhow::Result<textures::cubemap::CubemapComponent> {
+    let binary_data = load_binary(file_name).await?;
+    let cubemap = textures::cubemap::Cubemap::from_hdr_bytes(&binary_data, device, queue)?;
+    Ok(create_skybox(cubemap, device, layout))
+}
+
+fn create_skybox(
+    cubemap: textures::cubemap::Cubemap,
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout
+) -> textures::cubemap::CubemapComponent {
     let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
         layout,
@@ -164,25 +324,30 @@ pub async fn load_cubemap_files(
             }
         ]
     });
-    let vertices = textures::cubemap::create_cubemap_vertices(device);
-    Ok(textures::cubemap::CubemapComponent { vertices, bind_group })
+    textures::cubemap::CubemapComponent { cubemap, bind_group }
 }
 
 pub async fn load_gltf(
     file_name: &str,
-    device: &wgpu::Device
-) -> anyhow::Result<model::Model> {
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pbr_layout: &wgpu::BindGroupLayout
+) -> anyhow::Result<(model::Model, textures::pbr::PbrMaterialComponent)> {
     let gltf_data = load_binary(file_name).await?;
-    let (doc, buffers, _images) = gltf::import_slice(&gltf_data)?;
+    let (doc, buffers, images) = gltf::import_slice(&gltf_data)?;
     let mut meshes = Vec::new();
 
+    // primitives without a material use the glTF default material,
+    // which is appended after the ones the document declares
+    let default_material = doc.materials().len();
+
     for mesh in doc.meshes() {
         for primitive in mesh.primitives() {
             let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
             let mut indices = Vec::new();
-            if let Some(gltf::mesh::util::ReadIndices::U16(iter)) = reader.read_indices() {
-                for index in iter {
-                    indices.push(index as u32);
+            if let Some(iter) = reader.read_indices() {
+                for index in iter.into_u32() {
+                    indices.push(index);
                 }
             }
             let mut positions = Vec::new();
@@ -198,8 +363,8 @@ pub async fn load_gltf(
                 }
             }
             let mut tex_coords = Vec::new();
-            if let Some(gltf::mesh::util::ReadTexCoords::F32(iter)) = reader.read_tex_coords(0) {
-                for uv in iter {
+            if let Some(iter) = reader.read_tex_coords(0) {
+                for uv in iter.into_f32() {
                     tex_coords.push(uv);
                 }
             }
@@ -221,6 +386,7 @@ pub async fn load_gltf(
                     normal,
                     uv
             }).collect::<Vec<_>>();
+            let bounds = model::Aabb::from_vertices(&vertices);
 
             let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Vertex Buffer", file_name)),
@@ -237,10 +403,141 @@ pub async fn load_gltf(
                 _name: file_name.to_string(),
                 vertex_buffer,
                 index_buffer,
-                num_elements: indices.len() as u32
+                num_elements: indices.len() as u32,
+                material: primitive.material().index().unwrap_or(default_material),
+                bounds,
+                sphere: model::BoundingSphere::from_vertices(&vertices, &bounds)
             });
         }
     }
 
-    Ok(model::Model { meshes })
+    let mut materials = Vec::new();
+    for material in doc.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        let load = |texture: Option<gltf::texture::Texture>, srgb: bool| {
+            texture.map(|texture| {
+                let image = &images[texture.source().index()];
+                let mut loaded = gltf_image_to_texture(image, device, queue, srgb, file_name)?;
+                loaded.sampler = textures::pbr::create_sampler(device, Some(&texture.sampler()));
+                anyhow::Ok(loaded)
+            }).transpose()
+        };
+
+        let textures = textures::pbr::PbrTextures {
+            base_color: load(pbr.base_color_texture().map(|t| t.texture()), true)?,
+            metallic_roughness: load(pbr.metallic_roughness_texture().map(|t| t.texture()), false)?,
+            normal: load(material.normal_texture().map(|t| t.texture()), false)?,
+            occlusion: load(material.occlusion_texture().map(|t| t.texture()), false)?,
+            emissive: load(material.emissive_texture().map(|t| t.texture()), true)?
+        };
+        let factors = uniform::PbrMaterialUniform {
+            base_color_factor: pbr.base_color_factor(),
+            emissive_factor: material.emissive_factor(),
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            occlusion_strength: material.occlusion_texture().map_or(1.0, |t| t.strength()),
+            normal_scale: material.normal_texture().map_or(1.0, |t| t.scale()),
+            ..Default::default()
+        };
+        materials.push(textures::pbr::PbrMaterial::new(
+            device, queue, pbr_layout, textures, factors, file_name
+        )?);
+    }
+    materials.push(textures::pbr::PbrMaterial::new(
+        device,
+        queue,
+        pbr_layout,
+        textures::pbr::PbrTextures::default(),
+        uniform::PbrMaterialUniform::default(),
+        file_name
+    )?);
+
+    Ok((model::Model { meshes }, textures::pbr::PbrMaterialComponent { materials }))
+}
+
+fn gltf_image_to_texture(
+    image: &gltf::image::Data,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    srgb: bool,
+    label: &str
+) -> anyhow::Result<textures::texture::Texture> {
+    use gltf::image::Format;
+    // only the most significant byte is kept for 16 bit formats
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        format => anyhow::bail!("Unsupported glTF image format in {}: {:?}", label, format)
+    };
+    let rgba = image.pixels
+        .chunks_exact(channels * bytes_per_channel)
+        .flat_map(|pixel| {
+            let channel = |i: usize| pixel[i * bytes_per_channel + bytes_per_channel - 1];
+            match channels {
+                1 => [channel(0), channel(0), channel(0), 255],
+                2 => [channel(0), channel(1), 0, 255],
+                3 => [channel(0), channel(1), channel(2), 255],
+                _ => [channel(0), channel(1), channel(2), channel(3)]
+            }
+        }).collect::<Vec<_>>();
+    textures::texture::Texture::from_rgba8(
+        device, queue, &rgba, (image.width, image.height), srgb, Some(label)
+    )
+}
+
+pub async fn load_pbr_material(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+    file_names: [Option<&str>; 5],
+    factors: uniform::PbrMaterialUniform
+) -> anyhow::Result<textures::pbr::PbrMaterial> {
+    // base color and emissive hold colors, the rest hold linear data
+    let srgb = [true, false, false, false, true];
+    let mut loaded = Vec::new();
+    for (file_name, srgb) in file_names.iter().zip(srgb) {
+        let texture = match file_name {
+            Some(file_name) => {
+                let mut texture = if srgb {
+                    load_texture(file_name, device, queue).await?
+                } else {
+                    load_linear_texture(file_name, device, queue).await?
+                };
+                texture.sampler = textures::pbr::create_sampler(device, None);
+                Some(texture)
+            }
+            None => None
+        };
+        loaded.push(texture);
+    }
+    let mut loaded = loaded.into_iter();
+    let textures = textures::pbr::PbrTextures {
+        base_color: loaded.next().flatten(),
+        metallic_roughness: loaded.next().flatten(),
+        normal: loaded.next().flatten(),
+        occlusion: loaded.next().flatten(),
+        emissive: loaded.next().flatten()
+    };
+    textures::pbr::PbrMaterial::new(
+        device, queue, layout, textures, factors, file_names[0].unwrap_or("Pbr Material")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_stems_stay_in_the_folder() {
+        assert_eq!(file_stem("recording_1700000000"), "recording_1700000000");
+        assert_eq!(file_stem("../../etc/passwd"), "______etc_passwd");
+        assert_eq!(file_stem("fly by: intro"), "fly_by__intro");
+        assert_eq!(file_stem(""), "path");
+    }
 }
@@ -0,0 +1,148 @@
+use serde_json::Value;
+use super::pipeline::DepthMode;
+
+// engine options read from res/settings.json, anything missing falls
+// back to the defaults picked in code
+#[derive(Default, Clone)]
+pub struct Settings {
+    post_processing: Value,
+    ssao: Value,
+    msaa: Value,
+    reverse_z: Value,
+    resolution: Value,
+    simulation: Value
+}
+
+impl Settings {
+    // settings block of a post processing effect, null when not present
+    pub fn effect(&self, name: &str) -> &Value {
+        &self.post_processing[name]
+    }
+
+    pub fn ssao(&self) -> &Value {
+        &self.ssao
+    }
+
+    // requested samples per pixel, the adapter may not support all of them
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa.as_u64().map_or(1, |samples| samples.max(1) as u32)
+    }
+
+    pub fn depth_mode(&self) -> DepthMode {
+        match self.reverse_z.as_bool() {
+            Some(true) => DepthMode::Reverse,
+            _ => DepthMode::Standard
+        }
+    }
+
+    // size of the render targets before upscaling to the window
+    pub fn internal_resolution(&self) -> (u32, u32) {
+        let size = |name, default| self.resolution[name].as_u64().map_or(default, |size| size.max(1) as u32);
+        (size("width", 480), size("height", 270))
+    }
+
+    // the filter and sharpness of the upscaler are read from here
+    pub fn resolution(&self) -> &Value {
+        &self.resolution
+    }
+
+    pub fn upscale_mode(&self) -> Option<&str> {
+        self.resolution["upscale"].as_str()
+    }
+
+    pub fn dynamic_resolution(&self) -> &Value {
+        &self.resolution["dynamic"]
+    }
+
+    // tick rate of the fixed timestep and how many ticks a frame can catch up
+    pub fn simulation(&self) -> &Value {
+        &self.simulation
+    }
+
+    // scenes can set any effect key, those win over the settings file.
+    // blocks that aren't objects are skipped with a warning
+    pub fn with_scene_overrides(&self, post_processing: &Value) -> Settings {
+        let mut settings = self.clone();
+        if post_processing.is_null() {
+            return settings;
+        }
+        let Some(scene_effects) = post_processing.as_object() else {
+            log::warn!("Scene post_processing isn't an object, ignoring it");
+            return settings;
+        };
+        if !settings.post_processing.is_object() {
+            if !settings.post_processing.is_null() {
+                log::warn!("post_processing in the settings file isn't an object, ignoring it");
+            }
+            settings.post_processing = Value::Object(Default::default());
+        }
+
+        let effects = settings.post_processing.as_object_mut().unwrap();
+        for (name, scene_effect) in scene_effects {
+            let Some(scene_effect) = scene_effect.as_object() else {
+                log::warn!("Scene override for {} isn't an object, ignoring it", name);
+                continue;
+            };
+            let effect = effects.entry(name.clone()).or_insert_with(|| Value::Object(Default::default()));
+            if !effect.is_object() {
+                *effect = Value::Object(Default::default());
+            }
+            let effect = effect.as_object_mut().unwrap();
+            for (key, value) in scene_effect {
+                effect.insert(key.clone(), value.clone());
+            }
+        }
+        settings
+    }
+}
+
+pub fn parse_settings(file: &str) -> anyhow::Result<Settings> {
+    let settings: Value = serde_json::from_str(file)?;
+
+    Ok(Settings {
+        post_processing: settings["post_processing"].clone(),
+        ssao: settings["ssao"].clone(),
+        msaa: settings["msaa"].clone(),
+        reverse_z: settings["reverse_z"].clone(),
+        resolution: settings["resolution"].clone(),
+        simulation: settings["simulation"].clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(post_processing: Value) -> Settings {
+        parse_settings(&json!({ "post_processing": post_processing }).to_string()).unwrap()
+    }
+
+    #[test]
+    fn scene_keys_win_and_the_rest_is_kept() {
+        let settings = settings(json!({ "bloom": { "intensity": 0.3, "knee": 0.5 } }))
+            .with_scene_overrides(&json!({ "bloom": { "intensity": 1.0 }, "palette": { "enabled": true } }));
+        assert_eq!(settings.effect("bloom"), &json!({ "intensity": 1.0, "knee": 0.5 }));
+        assert_eq!(settings.effect("palette"), &json!({ "enabled": true }));
+    }
+
+    #[test]
+    fn overrides_that_arent_objects_are_ignored() {
+        let base = settings(json!({ "bloom": { "intensity": 0.3 } }));
+        for overrides in [json!(null), json!([1, 2]), json!("bloom")] {
+            assert_eq!(base.with_scene_overrides(&overrides).effect("bloom"), &json!({ "intensity": 0.3 }));
+        }
+        let settings = base.with_scene_overrides(&json!({ "bloom": [1], "tonemap": { "exposure": 2.0 } }));
+        assert_eq!(settings.effect("bloom"), &json!({ "intensity": 0.3 }));
+        assert_eq!(settings.effect("tonemap"), &json!({ "exposure": 2.0 }));
+    }
+
+    #[test]
+    fn settings_blocks_that_arent_objects_are_replaced() {
+        let overrides = json!({ "bloom": { "intensity": 1.0 } });
+        for post_processing in [json!([1, 2]), json!({ "bloom": 5 })] {
+            let settings = settings(post_processing).with_scene_overrides(&overrides);
+            assert_eq!(settings.effect("bloom"), &json!({ "intensity": 1.0 }));
+        }
+    }
+}
@@ -0,0 +1,15 @@
+// shared by the fxaa and smaa passes, both find edges from the luma of
+// the tone mapped image
+struct AntiAliasParams {
+    mode: f32,
+    edge_threshold: f32,
+    subpixel: f32,
+    max_search: f32
+};
+@group(1) @binding(0)
+var<uniform> params: AntiAliasParams;
+
+// square root approximates the perceptual curve the thresholds are tuned for
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
@@ -0,0 +1,60 @@
+// shared by the bloom passes, group 0 is the texture being filtered and
+// group 2 the matching downsample when upsampling or compositing
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    radius: f32,
+    levels: f32
+};
+@group(1) @binding(0)
+var<uniform> params: BloomParams;
+
+@group(2) @binding(0)
+var t_detail: texture_2d<f32>;
+@group(2) @binding(1)
+var s_detail: sampler;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// weights a group of samples by inverse brightness, keeps single bright
+// pixels from flickering in and out of the bloom as the camera moves
+fn karis_average(a: vec3<f32>, b: vec3<f32>, c: vec3<f32>, d: vec3<f32>) -> vec3<f32> {
+    let wa = 1.0 / (1.0 + luminance(a));
+    let wb = 1.0 / (1.0 + luminance(b));
+    let wc = 1.0 / (1.0 + luminance(c));
+    let wd = 1.0 / (1.0 + luminance(d));
+    return (a * wa + b * wb + c * wc + d * wd) / (wa + wb + wc + wd);
+}
+
+// 13 tap downsample from "Next Generation Post Processing in Call of Duty"
+fn downsample(uv: vec2<f32>, karis: bool) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+    let a = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(-2.0, -2.0), 0.0).rgb;
+    let b = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(0.0, -2.0), 0.0).rgb;
+    let c = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(2.0, -2.0), 0.0).rgb;
+    let d = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(-2.0, 0.0), 0.0).rgb;
+    let e = textureSampleLevel(t_input, s_input, uv, 0.0).rgb;
+    let f = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(2.0, 0.0), 0.0).rgb;
+    let g = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(-2.0, 2.0), 0.0).rgb;
+    let h = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(0.0, 2.0), 0.0).rgb;
+    let i = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(2.0, 2.0), 0.0).rgb;
+    let j = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(-1.0, -1.0), 0.0).rgb;
+    let k = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(1.0, -1.0), 0.0).rgb;
+    let l = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(-1.0, 1.0), 0.0).rgb;
+    let m = textureSampleLevel(t_input, s_input, uv + texel * vec2<f32>(1.0, 1.0), 0.0).rgb;
+
+    if karis {
+        return karis_average(j, k, l, m) * 0.5
+            + karis_average(a, b, d, e) * 0.125
+            + karis_average(b, c, e, f) * 0.125
+            + karis_average(d, e, g, h) * 0.125
+            + karis_average(e, f, h, i) * 0.125;
+    }
+    return e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
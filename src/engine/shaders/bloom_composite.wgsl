@@ -0,0 +1,8 @@
+// every level was summed on the way up, dividing by the level count keeps
+// the intensity independent of the resolution
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene = textureSample(t_input, s_input, in.uv);
+    let bloom = textureSample(t_detail, s_detail, in.uv).rgb / params.levels;
+    return vec4<f32>(scene.rgb + bloom * params.intensity, scene.a);
+}
@@ -0,0 +1,11 @@
+// first downsample of the scene, only keeps what is over the threshold
+// with a soft knee so the cutoff doesn't show
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = downsample(in.uv, true);
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - params.threshold + params.knee, 0.0, 2.0 * params.knee);
+    soft = soft * soft / (4.0 * params.knee + 0.0001);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
@@ -0,0 +1,18 @@
+// 3x3 tent filter over the smaller level, the radius spreads the taps
+// further apart in that level's texels
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let offset = params.radius / vec2<f32>(textureDimensions(t_input));
+    var sum = textureSampleLevel(t_input, s_input, in.uv, 0.0).rgb * 4.0;
+    sum += textureSampleLevel(t_input, s_input, in.uv + vec2<f32>(-offset.x, 0.0), 0.0).rgb * 2.0;
+    sum += textureSampleLevel(t_input, s_input, in.uv + vec2<f32>(offset.x, 0.0), 0.0).rgb * 2.0;
+    sum += textureSampleLevel(t_input, s_input, in.uv + vec2<f32>(0.0, -offset.y), 0.0).rgb * 2.0;
+    sum += textureSampleLevel(t_input, s_input, in.uv + vec2<f32>(0.0, offset.y), 0.0).rgb * 2.0;
+    sum += textureSampleLevel(t_input, s_input, in.uv + vec2<f32>(-offset.x, -offset.y), 0.0).rgb;
+    sum += textureSampleLevel(t_input, s_input, in.uv + vec2<f32>(offset.x, -offset.y), 0.0).rgb;
+    sum += textureSampleLevel(t_input, s_input, in.uv + vec2<f32>(-offset.x, offset.y), 0.0).rgb;
+    sum += textureSampleLevel(t_input, s_input, in.uv + offset, 0.0).rgb;
+
+    let detail = textureSampleLevel(t_detail, s_detail, in.uv, 0.0).rgb;
+    return vec4<f32>(sum / 16.0 + detail, 1.0);
+}
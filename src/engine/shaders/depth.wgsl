@@ -0,0 +1,22 @@
+// depth prepass, positions have to come out exactly like the lit shaders
+// so their LessEqual test passes against this depth
+struct CameraUniform {
+    view_projection: mat4x4<f32>
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ModelPush {
+    model: mat4x4<f32>
+}
+var<push_constant> push: ModelPush;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> @builtin(position) @invariant vec4<f32> {
+    let world_position = push.model * vec4<f32>(model.position, 1.0);
+    return camera.view_projection * world_position;
+}
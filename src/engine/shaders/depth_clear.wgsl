@@ -0,0 +1,13 @@
+// a triangle over the whole viewport at the far plane, FAR_DEPTH is
+// prepended for the depth mode
+@vertex
+fn vs_main(@builtin(vertex_index) id: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((id << 1u) & 2u), f32(id & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, FAR_DEPTH, 1.0);
+}
+
+// only there because the lit pass has a color target, its writes are masked
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0);
+}
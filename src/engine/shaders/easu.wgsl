@@ -0,0 +1,136 @@
+// fsr 1 edge adaptive spatial upsampling, a 12 tap lanczos-like kernel
+// stretched along the local edge direction, rendered at output size
+//
+//     b c
+//   e f g h
+//   i j k l
+//     n o
+
+// luma with green counted twice, cheap and close enough for the gradients
+fn easu_luma(color: vec3<f32>) -> f32 {
+    return color.b * 0.5 + color.r * 0.5 + color.g;
+}
+
+// direction and length of the edge around one of the four inner texels,
+// weighted by its bilinear weight
+fn edge(weight: f32, above: f32, left: f32, center: f32, right: f32, below: f32) -> vec3<f32> {
+    let horizontal = right - left;
+    var length_x = abs(horizontal) / max(max(abs(right - center), abs(center - left)), 1e-5);
+    length_x = saturate(length_x);
+    let vertical = below - above;
+    var length_y = abs(vertical) / max(max(abs(below - center), abs(center - above)), 1e-5);
+    length_y = saturate(length_y);
+    return vec3<f32>(horizontal, vertical, length_x * length_x + length_y * length_y) * weight;
+}
+
+struct Accumulator {
+    color: vec3<f32>,
+    weight: f32
+};
+
+fn tap(
+    accumulator: ptr<function, Accumulator>,
+    offset: vec2<f32>,
+    direction: vec2<f32>,
+    stretch: vec2<f32>,
+    lobe: f32,
+    clip: f32,
+    color: vec3<f32>
+) {
+    // rotate into the edge direction and scale the kernel along it
+    var v = vec2<f32>(
+        offset.x * direction.x + offset.y * direction.y,
+        offset.x * -direction.y + offset.y * direction.x
+    ) * stretch;
+    let distance2 = min(dot(v, v), clip);
+    // polynomial stand-in for lanczos 2, window times lobe
+    var window = 0.4 * distance2 - 1.0;
+    var base = lobe * distance2 - 1.0;
+    window *= window;
+    base *= base;
+    window = 1.5625 * window - 0.5625;
+    let weight = window * base;
+    (*accumulator).color += color * weight;
+    (*accumulator).weight += weight;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scale = vec2<f32>(params.source_width / params.output_width, params.source_height / params.output_height);
+    // source position of the output pixel center, split into the top left
+    // texel of the bilinear footprint and the fraction past it
+    let position = in.clip_position.xy * scale - 0.5;
+    let origin = vec2<i32>(floor(position));
+    let fraction = position - floor(position);
+
+    let b = load_clamped(origin + vec2<i32>(0, -1));
+    let c = load_clamped(origin + vec2<i32>(1, -1));
+    let e = load_clamped(origin + vec2<i32>(-1, 0));
+    let f = load_clamped(origin);
+    let g = load_clamped(origin + vec2<i32>(1, 0));
+    let h = load_clamped(origin + vec2<i32>(2, 0));
+    let i = load_clamped(origin + vec2<i32>(-1, 1));
+    let j = load_clamped(origin + vec2<i32>(0, 1));
+    let k = load_clamped(origin + vec2<i32>(1, 1));
+    let l = load_clamped(origin + vec2<i32>(2, 1));
+    let n = load_clamped(origin + vec2<i32>(0, 2));
+    let o = load_clamped(origin + vec2<i32>(1, 2));
+
+    let bl = easu_luma(b);
+    let cl = easu_luma(c);
+    let el = easu_luma(e);
+    let fl = easu_luma(f);
+    let gl = easu_luma(g);
+    let hl = easu_luma(h);
+    let il = easu_luma(i);
+    let jl = easu_luma(j);
+    let kl = easu_luma(k);
+    let ll = easu_luma(l);
+    let nl = easu_luma(n);
+    let ol = easu_luma(o);
+
+    let x = fraction.x;
+    let y = fraction.y;
+    let gradient = edge((1.0 - x) * (1.0 - y), bl, el, fl, gl, jl)
+        + edge(x * (1.0 - y), cl, fl, gl, hl, kl)
+        + edge((1.0 - x) * y, fl, il, jl, kl, nl)
+        + edge(x * y, gl, jl, kl, ll, ol);
+
+    // flat areas get an arbitrary direction, the length keeps them round
+    var direction = gradient.xy;
+    let direction_length2 = dot(direction, direction);
+    if direction_length2 < 1.0 / 32768.0 {
+        direction = vec2<f32>(1.0, 0.0);
+    } else {
+        direction *= inverseSqrt(direction_length2);
+    }
+    var edge_length = gradient.z * 0.5;
+    edge_length *= edge_length;
+
+    // diagonals stretch further so the kernel reaches the next texel
+    let diagonal = dot(direction, direction) / max(abs(direction.x), abs(direction.y));
+    let stretch = vec2<f32>(1.0 + (diagonal - 1.0) * edge_length, 1.0 - 0.5 * edge_length);
+    // sharper negative lobe on edges, softer in flat areas
+    let lobe = 0.5 + ((1.0 / 4.0 - 0.04) - 0.5) * edge_length;
+    let clip = 1.0 / lobe;
+
+    var accumulator = Accumulator(vec3<f32>(0.0), 0.0);
+    tap(&accumulator, vec2<f32>(0.0, -1.0) - fraction, direction, stretch, lobe, clip, b);
+    tap(&accumulator, vec2<f32>(1.0, -1.0) - fraction, direction, stretch, lobe, clip, c);
+    tap(&accumulator, vec2<f32>(-1.0, 1.0) - fraction, direction, stretch, lobe, clip, i);
+    tap(&accumulator, vec2<f32>(0.0, 1.0) - fraction, direction, stretch, lobe, clip, j);
+    tap(&accumulator, vec2<f32>(0.0, 0.0) - fraction, direction, stretch, lobe, clip, f);
+    tap(&accumulator, vec2<f32>(-1.0, 0.0) - fraction, direction, stretch, lobe, clip, e);
+    tap(&accumulator, vec2<f32>(1.0, 1.0) - fraction, direction, stretch, lobe, clip, k);
+    tap(&accumulator, vec2<f32>(2.0, 1.0) - fraction, direction, stretch, lobe, clip, l);
+    tap(&accumulator, vec2<f32>(2.0, 0.0) - fraction, direction, stretch, lobe, clip, h);
+    tap(&accumulator, vec2<f32>(1.0, 0.0) - fraction, direction, stretch, lobe, clip, g);
+    tap(&accumulator, vec2<f32>(1.0, 2.0) - fraction, direction, stretch, lobe, clip, o);
+    tap(&accumulator, vec2<f32>(0.0, 2.0) - fraction, direction, stretch, lobe, clip, n);
+
+    // clamped to the inner four texels so the negative lobes can't ring
+    let lowest = min(min(f, g), min(j, k));
+    let highest = max(max(f, g), max(j, k));
+    let color = clamp(accumulator.color / accumulator.weight, lowest, highest);
+    return vec4<f32>(color, 1.0);
+}
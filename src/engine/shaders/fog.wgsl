@@ -0,0 +1,65 @@
+// distance and height fog shared by the lit and skybox shaders, which
+// declare the `fog` uniform next to the environment textures it samples
+// (`t_prefiltered` and `s_environment`)
+struct FogUniform {
+    color: vec3<f32>,
+    // 0 off, 1 linear, 2 exponential, 3 exponential squared
+    mode: u32,
+    density: f32,
+    start: f32,
+    end: f32,
+    from_skybox: u32,
+    height_density: f32,
+    height_falloff: f32,
+    height_base: f32,
+    // how far away the skybox counts as
+    sky_distance: f32
+};
+
+// how much of the color along a ray of this length is replaced by fog
+fn fog_amount(distance: f32, origin_height: f32, direction_y: f32) -> f32 {
+    var amount = 0.0;
+    switch fog.mode {
+        case 1u: {
+            amount = clamp((distance - fog.start) / max(fog.end - fog.start, 0.0001), 0.0, 1.0);
+        }
+        case 2u: {
+            amount = 1.0 - exp(-fog.density * distance);
+        }
+        case 3u: {
+            let depth = fog.density * distance;
+            amount = 1.0 - exp(-depth * depth);
+        }
+        default: {}
+    }
+
+    // density falls off exponentially above the base height, integrated
+    // along the ray and layered over the distance fog
+    if fog.height_density > 0.0 {
+        let falloff = max(fog.height_falloff, 0.0001);
+        var optical_depth = fog.height_density * exp(-falloff * (origin_height - fog.height_base)) * distance;
+        let slope = falloff * direction_y * distance;
+        if abs(slope) > 0.0001 {
+            optical_depth *= (1.0 - exp(-slope)) / slope;
+        }
+        amount = 1.0 - (1.0 - amount) * exp(-optical_depth);
+    }
+    return amount;
+}
+
+// the roughest prefiltered mip is a blurred sky, so fog picks up the
+// colors of the horizon it is looking towards
+fn fog_color(direction: vec3<f32>) -> vec3<f32> {
+    if fog.from_skybox == 0u {
+        return fog.color;
+    }
+    let max_lod = f32(textureNumLevels(t_prefiltered) - 1u);
+    return textureSampleLevel(t_prefiltered, s_environment, direction, max_lod).rgb;
+}
+
+fn apply_fog(color: vec3<f32>, origin: vec3<f32>, ray: vec3<f32>) -> vec3<f32> {
+    let distance = length(ray);
+    let direction = ray / max(distance, 0.0001);
+    let amount = fog_amount(distance, origin.y, direction.y);
+    return mix(color, fog_color(direction), amount);
+}
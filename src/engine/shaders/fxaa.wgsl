@@ -0,0 +1,142 @@
+// fxaa 3.11 quality preset: finds the direction of the edge, walks along
+// it to both ends and resamples the pixel shifted towards the far side
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+const ITERATIONS: i32 = 12;
+
+// the walk speeds up the further it gets from the pixel
+fn step_scale(i: i32) -> f32 {
+    if i < 5 {
+        return 1.0;
+    } else if i == 5 {
+        return 1.5;
+    } else if i < 10 {
+        return 2.0;
+    } else if i == 10 {
+        return 4.0;
+    }
+    return 8.0;
+}
+
+fn sample_luma(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(t_input, s_input, uv, 0.0).rgb);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_input));
+    let uv = in.uv;
+    let center = textureSampleLevel(t_input, s_input, uv, 0.0);
+
+    let luma_center = luma(center.rgb);
+    let luma_up = sample_luma(uv + vec2<f32>(0.0, -1.0) * texel);
+    let luma_down = sample_luma(uv + vec2<f32>(0.0, 1.0) * texel);
+    let luma_left = sample_luma(uv + vec2<f32>(-1.0, 0.0) * texel);
+    let luma_right = sample_luma(uv + vec2<f32>(1.0, 0.0) * texel);
+
+    let luma_min = min(luma_center, min(min(luma_up, luma_down), min(luma_left, luma_right)));
+    let luma_max = max(luma_center, max(max(luma_up, luma_down), max(luma_left, luma_right)));
+    let range = luma_max - luma_min;
+    if range < max(EDGE_THRESHOLD_MIN, luma_max * params.edge_threshold) {
+        return center;
+    }
+
+    let luma_up_left = sample_luma(uv + vec2<f32>(-1.0, -1.0) * texel);
+    let luma_up_right = sample_luma(uv + vec2<f32>(1.0, -1.0) * texel);
+    let luma_down_left = sample_luma(uv + vec2<f32>(-1.0, 1.0) * texel);
+    let luma_down_right = sample_luma(uv + vec2<f32>(1.0, 1.0) * texel);
+
+    let up_down = luma_up + luma_down;
+    let left_right = luma_left + luma_right;
+    let left_corners = luma_up_left + luma_down_left;
+    let right_corners = luma_up_right + luma_down_right;
+    let up_corners = luma_up_left + luma_up_right;
+    let down_corners = luma_down_left + luma_down_right;
+
+    let edge_horizontal = abs(-2.0 * luma_left + left_corners)
+        + abs(-2.0 * luma_center + up_down) * 2.0
+        + abs(-2.0 * luma_right + right_corners);
+    let edge_vertical = abs(-2.0 * luma_up + up_corners)
+        + abs(-2.0 * luma_center + left_right) * 2.0
+        + abs(-2.0 * luma_down + down_corners);
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    // which side of the pixel the edge is on
+    let luma_negative = select(luma_left, luma_up, is_horizontal);
+    let luma_positive = select(luma_right, luma_down, is_horizontal);
+    let gradient_negative = luma_negative - luma_center;
+    let gradient_positive = luma_positive - luma_center;
+    let negative_steepest = abs(gradient_negative) >= abs(gradient_positive);
+    let gradient_scaled = 0.25 * max(abs(gradient_negative), abs(gradient_positive));
+
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var luma_local_average = 0.5 * (luma_positive + luma_center);
+    if negative_steepest {
+        step_length = -step_length;
+        luma_local_average = 0.5 * (luma_negative + luma_center);
+    }
+
+    // walk along the edge from halfway between the pixel and its neighbour
+    var edge_uv = uv;
+    if is_horizontal {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+    let offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    var uv1 = edge_uv - offset;
+    var uv2 = edge_uv + offset;
+    var luma_end1 = sample_luma(uv1) - luma_local_average;
+    var luma_end2 = sample_luma(uv2) - luma_local_average;
+    var reached1 = abs(luma_end1) >= gradient_scaled;
+    var reached2 = abs(luma_end2) >= gradient_scaled;
+    if !reached1 {
+        uv1 -= offset;
+    }
+    if !reached2 {
+        uv2 += offset;
+    }
+    for (var i = 2; i < ITERATIONS; i++) {
+        if reached1 && reached2 {
+            break;
+        }
+        if !reached1 {
+            luma_end1 = sample_luma(uv1) - luma_local_average;
+        }
+        if !reached2 {
+            luma_end2 = sample_luma(uv2) - luma_local_average;
+        }
+        reached1 = abs(luma_end1) >= gradient_scaled;
+        reached2 = abs(luma_end2) >= gradient_scaled;
+        if !reached1 {
+            uv1 -= offset * step_scale(i);
+        }
+        if !reached2 {
+            uv2 += offset * step_scale(i);
+        }
+    }
+
+    let distance1 = select(uv.y - uv1.y, uv.x - uv1.x, is_horizontal);
+    let distance2 = select(uv2.y - uv.y, uv2.x - uv.x, is_horizontal);
+    let closer_to_1 = distance1 < distance2;
+    let distance_final = min(distance1, distance2);
+    let edge_length = distance1 + distance2;
+
+    // only shift when the closer end goes the other way than the center
+    let luma_end = select(luma_end2, luma_end1, closer_to_1);
+    let correct_variation = (luma_end < 0.0) != (luma_center < luma_local_average);
+    var final_offset = select(0.0, 0.5 - distance_final / edge_length, correct_variation);
+
+    // subpixel aliasing, single pixel details that the edge walk misses
+    let luma_average = (1.0 / 12.0) * (2.0 * (up_down + left_right) + left_corners + right_corners);
+    let subpixel_1 = clamp(abs(luma_average - luma_center) / range, 0.0, 1.0);
+    let subpixel_2 = (-2.0 * subpixel_1 + 3.0) * subpixel_1 * subpixel_1;
+    final_offset = max(final_offset, subpixel_2 * subpixel_2 * params.subpixel);
+
+    var final_uv = uv;
+    if is_horizontal {
+        final_uv.y += final_offset * step_length;
+    } else {
+        final_uv.x += final_offset * step_length;
+    }
+    return textureSampleLevel(t_input, s_input, final_uv, 0.0);
+}
@@ -0,0 +1,38 @@
+// scale and bias applied to f0 by the split sum approximation, indexed by
+// n dot v along x and roughness along y
+const SAMPLE_COUNT: u32 = 1024u;
+
+fn geometry_schlick_ggx(n_dot_v: f32, roughness: f32) -> f32 {
+    // image based lighting uses a different k than direct lighting
+    let k = (roughness * roughness) / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n_dot_v = max(in.uv.x, 0.0001);
+    let roughness = in.uv.y;
+    let view = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let normal = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let xi = hammersley(i, SAMPLE_COUNT);
+        let half_direction = importance_sample_ggx(xi, normal, roughness);
+        let light = normalize(2.0 * dot(view, half_direction) * half_direction - view);
+
+        let n_dot_l = max(light.z, 0.0);
+        let n_dot_h = max(half_direction.z, 0.0);
+        let v_dot_h = max(dot(view, half_direction), 0.0);
+        if n_dot_l > 0.0 {
+            let geometry = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            let visibility = geometry * v_dot_h / (n_dot_h * n_dot_v + 0.0001);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+
+    return vec4<f32>(scale / f32(SAMPLE_COUNT), bias / f32(SAMPLE_COUNT), 0.0, 1.0);
+}
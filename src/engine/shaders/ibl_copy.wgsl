@@ -0,0 +1,12 @@
+// copies a cube face, or builds the next mip level of one when the
+// source view is the previous mip
+@group(0) @binding(0)
+var t_source: texture_cube<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = face_direction(push.face, in.uv);
+    return vec4<f32>(textureSampleLevel(t_source, s_source, direction, 0.0).rgb, 1.0);
+}
@@ -0,0 +1,15 @@
+// samples an equirectangular panorama in the direction of each cube texel
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = face_direction(push.face, in.uv);
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI
+    );
+    return vec4<f32>(textureSampleLevel(t_source, s_source, uv, 0.0).rgb, 1.0);
+}
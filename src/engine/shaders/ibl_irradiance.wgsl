@@ -0,0 +1,37 @@
+@group(0) @binding(0)
+var t_source: texture_cube<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+const SAMPLE_DELTA: f32 = 0.025;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = face_direction(push.face, in.uv);
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if abs(normal.y) > 0.999 {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let right = normalize(cross(up, normal));
+    up = cross(normal, right);
+
+    // read from the mip whose texels cover about as much of the sphere as
+    // one sample does, so the sparse samples don't alias
+    let texel_solid_angle = 4.0 * PI / (6.0 * push.source_resolution * push.source_resolution);
+    let sample_solid_angle = SAMPLE_DELTA * SAMPLE_DELTA;
+    let level = max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+
+    var irradiance = vec3<f32>(0.0);
+    var sample_count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PI; phi += SAMPLE_DELTA) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta += SAMPLE_DELTA) {
+            let tangent_sample = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let direction = tangent_sample.x * right + tangent_sample.y * up + tangent_sample.z * normal;
+            irradiance += textureSampleLevel(t_source, s_source, direction, level).rgb
+                * cos(theta) * sin(theta);
+            sample_count += 1.0;
+        }
+    }
+
+    return vec4<f32>(PI * irradiance / sample_count, 1.0);
+}
@@ -0,0 +1,48 @@
+@group(0) @binding(0)
+var t_source: texture_cube<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+
+const SAMPLE_COUNT: u32 = 512u;
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denominator * denominator);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = face_direction(push.face, in.uv);
+    if push.roughness <= 0.0 {
+        return vec4<f32>(textureSampleLevel(t_source, s_source, normal, 0.0).rgb, 1.0);
+    }
+    // split sum approximation, assumes the view direction equals the normal
+    let view = normal;
+    let texel_solid_angle = 4.0 * PI / (6.0 * push.source_resolution * push.source_resolution);
+
+    var color = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        let xi = hammersley(i, SAMPLE_COUNT);
+        let half_direction = importance_sample_ggx(xi, normal, push.roughness);
+        let light = normalize(2.0 * dot(view, half_direction) * half_direction - view);
+
+        let n_dot_l = dot(normal, light);
+        if n_dot_l > 0.0 {
+            // filtered importance sampling, low probability directions read
+            // from blurrier mips
+            let n_dot_h = max(dot(normal, half_direction), 0.0);
+            let h_dot_v = max(dot(half_direction, view), 0.0);
+            let pdf = distribution_ggx(n_dot_h, push.roughness) * n_dot_h / (4.0 * h_dot_v) + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(SAMPLE_COUNT) * pdf + 0.0001);
+            let level = max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+
+            color += textureSampleLevel(t_source, s_source, light, level).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+    }
+
+    return vec4<f32>(color / max(total_weight, 0.0001), 1.0);
+}
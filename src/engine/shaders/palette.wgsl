@@ -0,0 +1,68 @@
+struct PaletteParams {
+    dither: f32,
+    dither_strength: f32,
+    colors: f32
+};
+@group(1) @binding(0)
+var<uniform> params: PaletteParams;
+
+@group(2) @binding(0)
+var t_palette: texture_2d<f32>;
+@group(2) @binding(1)
+var t_noise: texture_2d<f32>;
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// recursive 8x8 bayer matrix from the bits of the pixel position
+fn bayer(pixel: vec2<u32>) -> f32 {
+    let x = pixel.x & 7u;
+    let xy = x ^ (pixel.y & 7u);
+    let index = ((xy & 1u) << 5u) | ((x & 1u) << 4u)
+        | ((xy & 2u) << 2u) | ((x & 2u) << 1u)
+        | ((xy & 4u) >> 1u) | ((x & 4u) >> 2u);
+    return (f32(index) + 0.5) / 64.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_input, s_input, in.uv);
+    // palettes are authored in srgb, so matching happens there too
+    var srgb = linear_to_srgb(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+
+    let pixel = vec2<u32>(in.clip_position.xy);
+    var threshold = 0.5;
+    switch u32(params.dither) {
+        case 1u: { threshold = bayer(pixel); }
+        case 2u: {
+            let noise_size = textureDimensions(t_noise);
+            threshold = textureLoad(t_noise, pixel % noise_size, 0).r;
+        }
+        default: {}
+    }
+    // roughly the distance between neighbouring colors of an evenly spread palette
+    let spread = params.dither_strength / pow(params.colors, 1.0 / 3.0);
+    srgb += (threshold - 0.5) * spread;
+
+    var closest = vec3<f32>(0.0);
+    var closest_distance = 1e6;
+    for (var i = 0u; i < u32(params.colors); i++) {
+        let candidate = textureLoad(t_palette, vec2<u32>(i, 0u), 0).rgb;
+        let difference = candidate - srgb;
+        let distance = dot(difference, difference);
+        if distance < closest_distance {
+            closest_distance = distance;
+            closest = candidate;
+        }
+    }
+    return vec4<f32>(srgb_to_linear(closest), color.a);
+}
@@ -0,0 +1,197 @@
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+    view_without_translation: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_position: vec4<f32>
+};
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct LightUniform {
+    direction: vec3<f32>,
+    color: vec3<f32>,
+};
+@group(2) @binding(0)
+var<uniform> light: LightUniform;
+
+struct ModelPush {
+    model: mat4x4<f32>
+}
+var<push_constant> push: ModelPush;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) normal: vec3<f32>
+};
+
+struct VertexOutput {
+    @builtin(position) @invariant clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let world_position = push.model * vec4<f32>(model.position, 1.0);
+    // transforms only use uniform scale, so the model matrix is fine for normals
+    out.world_normal = (push.model * vec4<f32>(model.normal, 0.0)).xyz;
+    out.world_position = world_position.xyz;
+    out.uv = model.uv;
+    out.clip_position = camera.view_projection * world_position;
+    return out;
+}
+
+struct PbrMaterialUniform {
+    base_color_factor: vec4<f32>,
+    emissive_factor: vec3<f32>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    occlusion_strength: f32,
+    normal_scale: f32
+};
+
+@group(0) @binding(0)
+var t_base_color: texture_2d<f32>;
+@group(0) @binding(1)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(4)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(5)
+var s_base_color: sampler;
+@group(0) @binding(6)
+var s_metallic_roughness: sampler;
+@group(0) @binding(7)
+var s_normal: sampler;
+@group(0) @binding(8)
+var s_occlusion: sampler;
+@group(0) @binding(9)
+var s_emissive: sampler;
+@group(0) @binding(10)
+var<uniform> material: PbrMaterialUniform;
+
+@group(3) @binding(0)
+var t_irradiance: texture_cube<f32>;
+@group(3) @binding(1)
+var t_prefiltered: texture_cube<f32>;
+@group(3) @binding(2)
+var t_brdf_lut: texture_2d<f32>;
+@group(3) @binding(3)
+var s_environment: sampler;
+// visibility from the ssao pass, same resolution as the render target
+@group(3) @binding(4)
+var t_ambient_occlusion: texture_2d<f32>;
+@group(3) @binding(5)
+var<uniform> fog: FogUniform;
+
+const PI: f32 = 3.14159265359;
+
+// meshes don't carry tangents, so the tangent frame is rebuilt from screen
+// space derivatives of the position and uv (Schüler, "Normal Mapping Without
+// Precomputed Tangents")
+fn perturb_normal(normal: vec3<f32>, position: vec3<f32>, uv: vec2<f32>, tangent_normal: vec3<f32>) -> vec3<f32> {
+    let dp1 = dpdx(position);
+    let dp2 = dpdy(position);
+    let duv1 = dpdx(uv);
+    let duv2 = dpdy(uv);
+
+    let dp2_perp = cross(dp2, normal);
+    let dp1_perp = cross(normal, dp1);
+    let tangent = dp2_perp * duv1.x + dp1_perp * duv2.x;
+    let bitangent = dp2_perp * duv1.y + dp1_perp * duv2.y;
+
+    let scale = max(dot(tangent, tangent), dot(bitangent, bitangent));
+    if scale <= 0.0 {
+        return normal;
+    }
+    let inverse_max = inverseSqrt(scale);
+    let tbn = mat3x3<f32>(tangent * inverse_max, bitangent * inverse_max, normal);
+    return normalize(tbn * tangent_normal);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denominator * denominator);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = (r * r) / 8.0;
+    let ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return ggx_v * ggx_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base_color = textureSample(t_base_color, s_base_color, in.uv) * material.base_color_factor;
+    let metallic_roughness = textureSample(t_metallic_roughness, s_metallic_roughness, in.uv);
+    let occlusion_sample = textureSample(t_occlusion, s_occlusion, in.uv).r;
+    let emissive = textureSample(t_emissive, s_emissive, in.uv).rgb * material.emissive_factor;
+    let normal_sample = textureSample(t_normal, s_normal, in.uv).xyz;
+
+    // glTF packs roughness in green and metallic in blue
+    let roughness = clamp(metallic_roughness.g * material.roughness_factor, 0.04, 1.0);
+    let metallic = clamp(metallic_roughness.b * material.metallic_factor, 0.0, 1.0);
+    let occlusion = 1.0 + material.occlusion_strength * (occlusion_sample - 1.0);
+
+    var normal = normalize(in.world_normal);
+    if material.normal_scale > 0.0 {
+        let tangent_normal = (normal_sample * 2.0 - 1.0) * vec3<f32>(material.normal_scale, material.normal_scale, 1.0);
+        normal = perturb_normal(normal, in.world_position, in.uv, normalize(tangent_normal));
+    }
+
+    let view_direction = normalize(camera.view_position.xyz - in.world_position);
+    let light_direction = normalize(light.direction);
+    let half_direction = normalize(light_direction + view_direction);
+
+    let n_dot_v = max(dot(normal, view_direction), 0.0001);
+    let n_dot_l = max(dot(normal, light_direction), 0.0);
+    let n_dot_h = max(dot(normal, half_direction), 0.0);
+    let h_dot_v = max(dot(half_direction, view_direction), 0.0);
+
+    let f0 = mix(vec3<f32>(0.04), base_color.rgb, metallic);
+    let fresnel = fresnel_schlick(h_dot_v, f0);
+    let specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * fresnel
+        / (4.0 * n_dot_v * max(n_dot_l, 0.0001));
+    let diffuse = (1.0 - fresnel) * (1.0 - metallic) * base_color.rgb / PI;
+
+    // a unit light scaled by PI keeps lambertian surfaces as bright as
+    // they are under the Blinn-Phong shader
+    let radiance = vec3<f32>(PI);
+    let direct = (diffuse + specular) * radiance * n_dot_l;
+
+    // split sum image based lighting
+    let ambient_fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+    let ambient_diffuse = (1.0 - ambient_fresnel) * (1.0 - metallic);
+    let reflection = reflect(-view_direction, normal);
+    let max_lod = f32(textureNumLevels(t_prefiltered) - 1u);
+    let irradiance = textureSample(t_irradiance, s_environment, normal).rgb;
+    let prefiltered = textureSampleLevel(t_prefiltered, s_environment, reflection, roughness * max_lod).rgb;
+    let brdf = textureSample(t_brdf_lut, s_environment, vec2<f32>(n_dot_v, roughness)).rg;
+    let screen_occlusion = textureLoad(t_ambient_occlusion, vec2<i32>(in.clip_position.xy), 0).r;
+    let ambient = (ambient_diffuse * irradiance * base_color.rgb
+        + prefiltered * (ambient_fresnel * brdf.x + brdf.y)) * occlusion * screen_occlusion;
+
+    let lit = ambient + direct + emissive;
+    let result = apply_fog(lit, camera.view_position.xyz, in.world_position - camera.view_position.xyz);
+
+    return vec4<f32>(result, base_color.a);
+}
@@ -0,0 +1,6 @@
+// shared by the post processing effects, reads the previous stage of the
+// chain (after fullscreen.wgsl)
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
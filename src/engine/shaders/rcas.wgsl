@@ -0,0 +1,31 @@
+// fsr 1 robust contrast adaptive sharpening on the easu output, the
+// sharpening lobe is limited so it never pushes past the neighbourhood
+//
+//     b
+//   d e f
+//     h
+const RCAS_LIMIT: f32 = 0.25 - 1.0 / 16.0;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the easu target covers just the viewport
+    let texel = vec2<i32>(in.clip_position.xy - vec2<f32>(params.output_x, params.output_y));
+    let b = load_clamped(texel + vec2<i32>(0, -1));
+    let d = load_clamped(texel + vec2<i32>(-1, 0));
+    let e = load_clamped(texel);
+    let f = load_clamped(texel + vec2<i32>(1, 0));
+    let h = load_clamped(texel + vec2<i32>(0, 1));
+
+    let lowest = min(min(b, d), min(f, h));
+    let highest = max(max(b, d), max(f, h));
+    // the largest negative lobe that keeps every channel inside [0, 1]
+    let hit_min = min(lowest, e) / (4.0 * highest + 1e-5);
+    let hit_max = (1.0 - max(highest, e)) / (4.0 * lowest - 4.0);
+    let lobe_rgb = max(-hit_min, hit_max);
+    // sharpness is in stops, 0 is the strongest
+    let lobe = max(-RCAS_LIMIT, min(max(lobe_rgb.r, max(lobe_rgb.g, lobe_rgb.b)), 0.0))
+        * exp2(-params.sharpness);
+
+    let color = (lobe * (b + d + f + h) + e) / (4.0 * lobe + 1.0);
+    return vec4<f32>(color, 1.0);
+}
@@ -1,29 +1,27 @@
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) uv: vec2<f32>
-};
-
-@vertex
-fn vs_main(@builtin(vertex_index) id: u32) -> VertexOutput {
-    var out: VertexOutput;
-    out.uv = vec2<f32>(
-        f32((id << 1u) & 2u),
-        f32(id & 2u)
-    );
-    out.clip_position = vec4<f32>(out.uv * 2.0 - 1.0, 0.0, 1.0);
-    out.uv.y = 1.0 - out.uv.y;
-    return out;
-}
-
-@group(0)
-@binding(0)
-var downscaled_image: texture_2d<f32>;
-
-@group(0)
-@binding(1)
-var downscaled_img_sampler: sampler;
+const FILTER_NEAREST: u32 = 0u;
+const FILTER_BILINEAR: u32 = 1u;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(downscaled_image, downscaled_img_sampler, in.uv);
-}
\ No newline at end of file
+    let source_size = vec2<f32>(params.source_width, params.source_height);
+    let texel = in.uv * source_size;
+
+    switch u32(params.filter_mode) {
+        case FILTER_NEAREST: {
+            return vec4<f32>(load_clamped(vec2<i32>(texel)), 1.0);
+        }
+        case FILTER_BILINEAR: {
+            return textureSampleLevel(t_input, s_input, in.uv, 0.0);
+        }
+        default: {
+            // sharp bilinear, nearest inside each source pixel and only
+            // blends over the one output pixel wide band at its border
+            let scale = vec2<f32>(params.output_width, params.output_height) / source_size;
+            let region = max(vec2<f32>(0.5) - 0.5 / scale, vec2<f32>(0.0));
+            let center_distance = fract(texel) - 0.5;
+            let offset = (center_distance - clamp(center_distance, -region, region)) * scale + 0.5;
+            let uv = (floor(texel) + offset) / source_size;
+            return textureSampleLevel(t_input, s_input, uv, 0.0);
+        }
+    }
+}
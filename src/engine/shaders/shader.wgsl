@@ -1,7 +1,8 @@
 struct CameraUniform {
     view_projection: mat4x4<f32>,
     view_without_translation: mat4x4<f32>,
-    projection: mat4x4<f32>
+    projection: mat4x4<f32>,
+    view_position: vec4<f32>
 };
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
@@ -25,38 +26,86 @@ struct VertexInput {
 };
 
 struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
+    @builtin(position) @invariant clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
-    @location(1) normal: vec3<f32>,
-    @location(2) vertex_light: f32
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>
 };
 
 @vertex
 fn vs_main(model: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
-    let light_direction = normalize(light.direction);
-    let normal_world_space = normalize((push.model * vec4<f32>(model.normal, 0)).xyz);
-    let light_intensity = max(dot(normal_world_space, light_direction), 0.0);
-    out.vertex_light = light_intensity;
-
+    let world_position = push.model * vec4<f32>(model.position, 1.0);
+    // transforms only use uniform scale, so the model matrix is fine for normals
+    out.world_normal = (push.model * vec4<f32>(model.normal, 0.0)).xyz;
+    out.world_position = world_position.xyz;
     out.uv = model.uv;
-    out.normal = model.normal;
-    out.clip_position = camera.view_projection * push.model * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_projection * world_position;
     return out;
 }
 
+struct MaterialUniform {
+    specular_color: vec3<f32>,
+    shininess: f32
+};
+
 @group(0) @binding(0)
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_specular: texture_2d<f32>;
+@group(0) @binding(3)
+var s_specular: sampler;
+@group(0) @binding(4)
+var<uniform> material: MaterialUniform;
+
+@group(3) @binding(0)
+var t_irradiance: texture_cube<f32>;
+@group(3) @binding(1)
+var t_prefiltered: texture_cube<f32>;
+@group(3) @binding(2)
+var t_brdf_lut: texture_2d<f32>;
+@group(3) @binding(3)
+var s_environment: sampler;
+// visibility from the ssao pass, same resolution as the render target
+@group(3) @binding(4)
+var t_ambient_occlusion: texture_2d<f32>;
+@group(3) @binding(5)
+var<uniform> fog: FogUniform;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let texture_color = textureSample(t_diffuse, s_diffuse, in.uv);
-    let ambient = light.color * 0.1;
-    let result = (ambient + in.vertex_light) * texture_color.xyz;
+    let specular_map = textureSample(t_specular, s_specular, in.uv);
+
+    let normal = normalize(in.world_normal);
+    let light_direction = normalize(light.direction);
+    let view_direction = normalize(camera.view_position.xyz - in.world_position);
+    let half_direction = normalize(light_direction + view_direction);
+
+    let diffuse = max(dot(normal, light_direction), 0.0);
+    // no highlight on faces pointing away from the light
+    let specular_strength = pow(max(dot(normal, half_direction), 0.0), material.shininess)
+        * step(0.0, dot(normal, light_direction));
+    let specular_color = material.specular_color * specular_map.rgb;
+    let specular = specular_color * specular_strength;
+
+    // image based ambient, the shininess is mapped to the roughness
+    // the prefiltered mips were generated for
+    let n_dot_v = max(dot(normal, view_direction), 0.0);
+    let roughness = sqrt(2.0 / (material.shininess + 2.0));
+    let reflection = reflect(-view_direction, normal);
+    let max_lod = f32(textureNumLevels(t_prefiltered) - 1u);
+    let irradiance = textureSample(t_irradiance, s_environment, normal).rgb;
+    let prefiltered = textureSampleLevel(t_prefiltered, s_environment, reflection, roughness * max_lod).rgb;
+    let brdf = textureSample(t_brdf_lut, s_environment, vec2<f32>(n_dot_v, roughness)).rg;
+    let occlusion = textureLoad(t_ambient_occlusion, vec2<i32>(in.clip_position.xy), 0).r;
+    let ambient = (irradiance * texture_color.xyz + prefiltered * specular_color * (brdf.x + brdf.y)) * occlusion;
+
+    let lit = ambient + diffuse * texture_color.xyz + specular;
+    let result = apply_fog(lit, camera.view_position.xyz, in.world_position - camera.view_position.xyz);
 
     return vec4<f32>(result, texture_color.a);
-    //return textureSample(t_diffuse, s_diffuse, in.uv);
 }
@@ -1,25 +1,31 @@
 struct CameraUniform {
     view_projection: mat4x4<f32>,
     view_without_translation: mat4x4<f32>,
-    projection: mat4x4<f32>
+    projection: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inverse_projection: mat4x4<f32>,
+    viewport: vec4<f32>,
+    far_depth: f32,
+    sky_extent: vec2<f32>
 };
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct VertexInput {
-    @location(0) position: vec3<f32>
-};
-
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) uvw: vec3<f32>
+    @location(0) ndc: vec2<f32>
 };
 
+// a triangle covering the viewport on the far plane, so only pixels
+// nothing was drawn to pass the depth test
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(@builtin(vertex_index) id: u32) -> VertexOutput {
     var out: VertexOutput;
-    out.clip_position = camera.projection * camera.view_without_translation * vec4(model.position, 1.0);
-    out.uvw = model.position;
+    out.ndc = vec2<f32>(
+        f32((id << 1u) & 2u),
+        f32(id & 2u)
+    ) * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, camera.far_depth, 1.0);
     return out;
 }
 
@@ -28,7 +34,28 @@ var t_sky: texture_cube<f32>;
 @group(0) @binding(1)
 var s_sky: sampler;
 
+@group(2) @binding(1)
+var t_prefiltered: texture_cube<f32>;
+@group(2) @binding(3)
+var s_environment: sampler;
+@group(2) @binding(5)
+var<uniform> fog: FogUniform;
+
+// the view ray through the camera's perspective fov, built from the
+// rotation alone so orthographic views and reverse z see the same sky
+fn view_direction(ndc: vec2<f32>) -> vec3<f32> {
+    let direction = vec3<f32>(ndc * camera.sky_extent, -1.0);
+    // the view matrix is only a rotation here, its transpose undoes it
+    let view = camera.view_without_translation;
+    let rotation = transpose(mat3x3<f32>(view[0].xyz, view[1].xyz, view[2].xyz));
+    return normalize(rotation * direction);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_sky, s_sky, in.uvw);
+    let direction = view_direction(in.ndc);
+    let sky = textureSample(t_sky, s_sky, direction);
+    // the sky sits at a fixed distance so dense fog can hide it
+    let ray = direction * fog.sky_distance;
+    return vec4<f32>(apply_fog(sky.rgb, camera.view_position.xyz, ray), sky.a);
 }
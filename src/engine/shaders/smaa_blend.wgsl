@@ -0,0 +1,45 @@
+// last smaa pass, neighbourhood blending. mixes every pixel with its
+// neighbours along whichever direction blends the most, by the weights
+// the pixel and its right and bottom neighbours hold
+@group(2) @binding(0)
+var t_weights: texture_2d<f32>;
+
+fn load_color(pixel: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_input));
+    return textureLoad(t_input, clamp(pixel, vec2<i32>(0), size - 1), 0);
+}
+
+fn load_weights(pixel: vec2<i32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(t_weights));
+    if any(pixel >= size) {
+        return vec4<f32>(0.0);
+    }
+    return textureLoad(t_weights, pixel, 0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let own = load_weights(pixel);
+    // right, bottom, left, top
+    let weights = vec4<f32>(
+        load_weights(pixel + vec2<i32>(1, 0)).a,
+        load_weights(pixel + vec2<i32>(0, 1)).g,
+        own.b,
+        own.r
+    );
+    let center = load_color(pixel);
+    if dot(weights, vec4<f32>(1.0)) < 1e-5 {
+        return center;
+    }
+
+    // the same as smaa's two bilinear fetches shifted by the weights
+    if max(weights.x, weights.z) > max(weights.y, weights.w) {
+        let right = mix(center, load_color(pixel + vec2<i32>(1, 0)), weights.x);
+        let left = mix(center, load_color(pixel + vec2<i32>(-1, 0)), weights.z);
+        return (right * weights.x + left * weights.z) / (weights.x + weights.z);
+    }
+    let bottom = mix(center, load_color(pixel + vec2<i32>(0, 1)), weights.y);
+    let top = mix(center, load_color(pixel + vec2<i32>(0, -1)), weights.w);
+    return (bottom * weights.y + top * weights.w) / (weights.y + weights.w);
+}
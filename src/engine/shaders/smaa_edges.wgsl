@@ -0,0 +1,33 @@
+// first smaa pass, luma edges on the left (r) and top (g) of every pixel
+fn load_luma(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_input));
+    return luma(textureLoad(t_input, clamp(pixel, vec2<i32>(0), size - 1), 0).rgb);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let center = load_luma(pixel);
+    let left = load_luma(pixel + vec2<i32>(-1, 0));
+    let top = load_luma(pixel + vec2<i32>(0, -1));
+
+    let delta = abs(center - vec2<f32>(left, top));
+    var edges = step(vec2<f32>(params.edge_threshold), delta);
+    if all(edges == vec2<f32>(0.0)) {
+        return vec4<f32>(0.0);
+    }
+
+    // local contrast adaptation, an edge less than half as strong as the
+    // strongest one around it is dropped
+    let right = load_luma(pixel + vec2<i32>(1, 0));
+    let bottom = load_luma(pixel + vec2<i32>(0, 1));
+    let left_left = load_luma(pixel + vec2<i32>(-2, 0));
+    let top_top = load_luma(pixel + vec2<i32>(0, -2));
+    let max_delta = max(
+        max(max(delta.x, delta.y), max(abs(center - right), abs(center - bottom))),
+        max(abs(left - left_left), abs(top - top_top))
+    );
+    edges *= step(vec2<f32>(max_delta), 2.0 * delta);
+
+    return vec4<f32>(edges, 0.0, 1.0);
+}
@@ -0,0 +1,165 @@
+// second smaa pass, follows every edge to its ends, reads the crossing
+// edges there and looks up how far the silhouette line through them bends
+// into the pixel in the area texture. the edges are read through a linear
+// sampler so a single fetch sees two pixels, the search texture turns the
+// last fetch of a search into how far the edge really went on.
+// writes: r pixel blends with its top neighbour, g the top neighbour
+// blends with the pixel, b and a the same for the left neighbour
+@group(2) @binding(0)
+var t_area: texture_2d<f32>;
+@group(2) @binding(1)
+var s_area: sampler;
+@group(3) @binding(0)
+var t_search: texture_2d<f32>;
+
+const AREA_MAX_DISTANCE: f32 = 16.0;
+const AREA_SIZE: f32 = 80.0;
+// how much of the blending survives at the corners of shapes
+const CORNER_ROUNDING: f32 = 0.25;
+// past this the distances don't fit the area texture anymore
+const MAX_SEARCH_STEPS: f32 = 112.0;
+
+fn texel_size() -> vec2<f32> {
+    return 1.0 / vec2<f32>(textureDimensions(t_input));
+}
+
+fn edges_at(uv: vec2<f32>) -> vec2<f32> {
+    return textureSampleLevel(t_input, s_input, uv, 0.0).rg;
+}
+
+// the crossing edges of a fetch pick the column, the edges being followed
+// the row. see smaa.rs for the layout
+fn search_length(e: vec2<f32>, right: bool) -> f32 {
+    let column = min(i32(round(32.0 * e.r)) + select(0, 33, right), 63);
+    let row = min(32 - i32(round(32.0 * e.g)), 15);
+    return textureLoad(t_search, vec2<i32>(column, row), 0).r;
+}
+
+// each search moves two pixels a step and stops once the fetch shows the
+// edge ending or a crossing edge, then backs off by what the search
+// texture says
+fn search_left(start: vec2<f32>, end: f32) -> f32 {
+    let texel = texel_size();
+    var uv = start;
+    var e = vec2<f32>(0.0, 1.0);
+    while uv.x > end && e.g > 0.8281 && e.r == 0.0 {
+        e = edges_at(uv);
+        uv.x -= 2.0 * texel.x;
+    }
+    let offset = 3.25 - (255.0 / 127.0) * search_length(e, false);
+    return uv.x + offset * texel.x;
+}
+
+fn search_right(start: vec2<f32>, end: f32) -> f32 {
+    let texel = texel_size();
+    var uv = start;
+    var e = vec2<f32>(0.0, 1.0);
+    while uv.x < end && e.g > 0.8281 && e.r == 0.0 {
+        e = edges_at(uv);
+        uv.x += 2.0 * texel.x;
+    }
+    let offset = 3.25 - (255.0 / 127.0) * search_length(e, true);
+    return uv.x - offset * texel.x;
+}
+
+fn search_up(start: vec2<f32>, end: f32) -> f32 {
+    let texel = texel_size();
+    var uv = start;
+    var e = vec2<f32>(1.0, 0.0);
+    while uv.y > end && e.r > 0.8281 && e.g == 0.0 {
+        e = edges_at(uv);
+        uv.y -= 2.0 * texel.y;
+    }
+    let offset = 3.25 - (255.0 / 127.0) * search_length(e.gr, false);
+    return uv.y + offset * texel.y;
+}
+
+fn search_down(start: vec2<f32>, end: f32) -> f32 {
+    let texel = texel_size();
+    var uv = start;
+    var e = vec2<f32>(1.0, 0.0);
+    while uv.y < end && e.r > 0.8281 && e.g == 0.0 {
+        e = edges_at(uv);
+        uv.y += 2.0 * texel.y;
+    }
+    let offset = 3.25 - (255.0 / 127.0) * search_length(e.gr, true);
+    return uv.y - offset * texel.y;
+}
+
+// the crossing edges were fetched a quarter pixel off so both sides of the
+// edge come out as different values, 4x those picks the pattern. the
+// distances are square rooted like the texture
+fn lookup_area(distance: vec2<f32>, e1: f32, e2: f32) -> vec2<f32> {
+    let coord = AREA_MAX_DISTANCE * round(4.0 * vec2<f32>(e1, e2)) + sqrt(distance);
+    return textureSampleLevel(t_area, s_area, (coord + 0.5) / AREA_SIZE, 0.0).rg;
+}
+
+// an edge ending in a crossing edge longer than a pixel is the corner of a
+// shape rather than a step of a slope, those get rounded less. only the
+// nearer end counts, both when the pixel is in the middle
+fn corner_factor(d: vec2<f32>, crossings: vec4<f32>) -> vec2<f32> {
+    let nearer = step(d.xy, d.yx);
+    let rounding = (1.0 - CORNER_ROUNDING) * nearer / (nearer.x + nearer.y);
+    let factor = vec2<f32>(
+        1.0 - rounding.x * crossings.x - rounding.y * crossings.y,
+        1.0 - rounding.x * crossings.z - rounding.y * crossings.w
+    );
+    return clamp(factor, vec2<f32>(0.0), vec2<f32>(1.0));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = texel_size();
+    let size = vec2<f32>(textureDimensions(t_input));
+    let uv = in.uv;
+    let pixel = uv * size;
+    let max_steps = min(params.max_search, MAX_SEARCH_STEPS);
+    let edges = edges_at(uv);
+    var weights = vec4<f32>(0.0);
+
+    // top edge, the crossing edges are left edges above or below it
+    if edges.g > 0.0 {
+        let start_left = uv + vec2<f32>(-0.25, -0.125) * texel;
+        let start_right = uv + vec2<f32>(1.25, -0.125) * texel;
+        let left = search_left(start_left, start_left.x - 2.0 * max_steps * texel.x);
+        let right = search_right(start_right, start_right.x + 2.0 * max_steps * texel.x);
+        let d = abs(round(vec2<f32>(left, right) * size.x - pixel.x));
+
+        let crossing_y = uv.y - 0.25 * texel.y;
+        let e1 = edges_at(vec2<f32>(left, crossing_y)).r;
+        let e2 = edges_at(vec2<f32>(right + texel.x, crossing_y)).r;
+        let area = lookup_area(d, e1, e2);
+
+        let crossings = vec4<f32>(
+            edges_at(vec2<f32>(left, uv.y + texel.y)).r,
+            edges_at(vec2<f32>(right + texel.x, uv.y + texel.y)).r,
+            edges_at(vec2<f32>(left, uv.y - 2.0 * texel.y)).r,
+            edges_at(vec2<f32>(right + texel.x, uv.y - 2.0 * texel.y)).r
+        );
+        weights = vec4<f32>(area * corner_factor(d, crossings), weights.ba);
+    }
+
+    // left edge, the crossing edges are top edges on either side of it
+    if edges.r > 0.0 {
+        let start_up = uv + vec2<f32>(-0.125, -0.25) * texel;
+        let start_down = uv + vec2<f32>(-0.125, 1.25) * texel;
+        let up = search_up(start_up, start_up.y - 2.0 * max_steps * texel.y);
+        let down = search_down(start_down, start_down.y + 2.0 * max_steps * texel.y);
+        let d = abs(round(vec2<f32>(up, down) * size.y - pixel.y));
+
+        let crossing_x = uv.x - 0.25 * texel.x;
+        let e1 = edges_at(vec2<f32>(crossing_x, up)).g;
+        let e2 = edges_at(vec2<f32>(crossing_x, down + texel.y)).g;
+        let area = lookup_area(d, e1, e2);
+
+        let crossings = vec4<f32>(
+            edges_at(vec2<f32>(uv.x + texel.x, up)).g,
+            edges_at(vec2<f32>(uv.x + texel.x, down + texel.y)).g,
+            edges_at(vec2<f32>(uv.x - 2.0 * texel.x, up)).g,
+            edges_at(vec2<f32>(uv.x - 2.0 * texel.x, down + texel.y)).g
+        );
+        weights = vec4<f32>(weights.rg, area * corner_factor(d, crossings));
+    }
+
+    return weights;
+}
@@ -0,0 +1,116 @@
+// hemisphere sampling around positions and normals reconstructed from
+// the depth prepass, written as an ambient visibility factor
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+    view_without_translation: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inverse_projection: mat4x4<f32>,
+    // x, y, width and height in pixels of the render target
+    viewport: vec4<f32>,
+    far_depth: f32
+};
+
+struct SsaoParams {
+    radius: f32,
+    strength: f32,
+    bias: f32
+};
+
+// bound as a plain float texture, gl can't read depth textures any other way
+@group(0) @binding(0)
+var t_depth: texture_2d<f32>;
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+@group(2) @binding(0)
+var<uniform> params: SsaoParams;
+
+const SAMPLE_COUNT: u32 = 16u;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+const TAU: f32 = 6.28318530;
+
+fn load_depth(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    return textureLoad(t_depth, clamp(pixel, vec2<i32>(0), size - 1), 0).r;
+}
+
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let uv = (vec2<f32>(pixel) + 0.5 - camera.viewport.xy) / camera.viewport.zw;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, load_depth(pixel), 1.0);
+    let position = camera.inverse_projection * ndc;
+    return position.xyz / position.w;
+}
+
+// takes the neighbour closer in depth on each axis so normals don't
+// bend around silhouettes
+fn view_normal(pixel: vec2<i32>, center: vec3<f32>) -> vec3<f32> {
+    let left = view_position(pixel - vec2<i32>(1, 0));
+    let right = view_position(pixel + vec2<i32>(1, 0));
+    let up = view_position(pixel - vec2<i32>(0, 1));
+    let down = view_position(pixel + vec2<i32>(0, 1));
+
+    var ddx = right - center;
+    if abs(center.z - left.z) < abs(right.z - center.z) {
+        ddx = center - left;
+    }
+    var ddy = down - center;
+    if abs(center.z - up.z) < abs(down.z - center.z) {
+        ddy = center - up;
+    }
+    // pixel rows run down the screen, so this faces the camera
+    return normalize(cross(ddy, ddx));
+}
+
+// 4x4 bayer matrix, the blur pass averages the pattern back out
+fn rotation_noise(pixel: vec2<i32>) -> f32 {
+    let p = vec2<u32>(pixel) & vec2<u32>(3u);
+    let m = p.x ^ p.y;
+    let index = ((m & 1u) << 3u) | ((p.y & 1u) << 2u) | (m & 2u) | ((p.y & 2u) >> 1u);
+    return f32(index) / 16.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    // nothing to occlude the sky
+    if load_depth(pixel) == camera.far_depth {
+        return vec4<f32>(1.0);
+    }
+
+    let position = view_position(pixel);
+    let normal = view_normal(pixel, position);
+    var helper = vec3<f32>(0.0, 1.0, 0.0);
+    if abs(normal.y) > 0.99 {
+        helper = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(helper, normal));
+    let bitangent = cross(normal, tangent);
+
+    let rotation = rotation_noise(pixel) * TAU;
+    var occlusion = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i++) {
+        // a spiral over the hemisphere, later samples reach further out
+        let t = (f32(i) + 0.5) / f32(SAMPLE_COUNT);
+        let cos_theta = sqrt(1.0 - t);
+        let sin_theta = sqrt(t);
+        let phi = f32(i) * GOLDEN_ANGLE + rotation;
+        let direction = tangent * (cos(phi) * sin_theta)
+            + bitangent * (sin(phi) * sin_theta)
+            + normal * cos_theta;
+        let scale = mix(0.1, 1.0, t * t);
+        let sample_position = position + direction * params.radius * scale;
+
+        let clip = camera.projection * vec4<f32>(sample_position, 1.0);
+        let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+        if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+            continue;
+        }
+        let scene_z = view_position(vec2<i32>(camera.viewport.xy + uv * camera.viewport.zw)).z;
+        // geometry far in front of the sample belongs to something else
+        let range = smoothstep(0.0, 1.0, params.radius / abs(position.z - scene_z));
+        occlusion += select(0.0, 1.0, scene_z >= sample_position.z + params.bias) * range;
+    }
+
+    let visibility = pow(1.0 - occlusion / f32(SAMPLE_COUNT), params.strength);
+    return vec4<f32>(visibility);
+}
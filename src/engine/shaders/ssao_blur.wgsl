@@ -0,0 +1,32 @@
+// box blur matching the 4x4 rotation pattern of the ssao pass, kept inside
+// the camera's viewport so it doesn't pick up what other cameras left
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+    view_without_translation: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    view_position: vec4<f32>,
+    inverse_projection: mat4x4<f32>,
+    // x, y, width and height in pixels of the render target
+    viewport: vec4<f32>,
+    far_depth: f32
+};
+
+@group(0) @binding(0)
+var t_occlusion: texture_2d<f32>;
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let low = vec2<i32>(camera.viewport.xy);
+    let high = low + vec2<i32>(camera.viewport.zw) - 1;
+    var sum = 0.0;
+    for (var y = -2; y < 2; y++) {
+        for (var x = -2; x < 2; x++) {
+            let neighbour = clamp(pixel + vec2<i32>(x, y), low, high);
+            sum += textureLoad(t_occlusion, neighbour, 0).r;
+        }
+    }
+    return vec4<f32>(sum / 16.0);
+}
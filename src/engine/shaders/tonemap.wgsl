@@ -0,0 +1,63 @@
+// exposure followed by one of the tone mapping operators, squeezes the
+// linear hdr scene into displayable range
+struct ToneMapParams {
+    exposure: f32,
+    mode: f32
+};
+@group(1) @binding(0)
+var<uniform> params: ToneMapParams;
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + color);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// polynomial approximation of the AgX base contrast curve
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4
+        - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104
+    );
+    let outset = mat3x3<f32>(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var encoded = log2(max(inset * color, vec3<f32>(1e-10)));
+    encoded = (clamp(encoded, vec3<f32>(min_ev), vec3<f32>(max_ev)) - min_ev) / (max_ev - min_ev);
+    encoded = outset * agx_contrast(encoded);
+    // the curve outputs display encoded values, the srgb surface expects linear
+    return pow(max(encoded, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureSample(t_input, s_input, in.uv).rgb * params.exposure;
+    var color: vec3<f32>;
+    switch u32(params.mode) {
+        case 0u: { color = reinhard(hdr); }
+        case 1u: { color = aces(hdr); }
+        default: { color = agx(hdr); }
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,25 @@
+// shared by the upscale filters (after fullscreen.wgsl), the input is the
+// final image of the post processing chain or the easu output for rcas
+@group(0) @binding(0)
+var t_input: texture_2d<f32>;
+@group(0) @binding(1)
+var s_input: sampler;
+
+// sizes are in pixels, the output is the viewport on the surface
+struct UpscaleParams {
+    filter_mode: f32,
+    sharpness: f32,
+    source_width: f32,
+    source_height: f32,
+    output_width: f32,
+    output_height: f32,
+    output_x: f32,
+    output_y: f32
+};
+@group(1) @binding(0)
+var<uniform> params: UpscaleParams;
+
+fn load_clamped(texel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(t_input));
+    return textureLoad(t_input, clamp(texel, vec2<i32>(0), size - 1), 0).rgb;
+}
@@ -0,0 +1,264 @@
+use super::{
+    postprocess::EffectParameters,
+    pipeline
+};
+use serde_json::Value;
+
+const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+// screen space ambient occlusion from the depth prepass, blurred into a
+// visibility texture the lit shaders multiply their ambient term with
+pub struct SsaoPipeline {
+    pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    parameters: EffectParameters,
+    depth_layout: wgpu::BindGroupLayout,
+    raw_layout: wgpu::BindGroupLayout,
+    depth_bind_group: wgpu::BindGroup,
+    raw_view: wgpu::TextureView,
+    raw_bind_group: wgpu::BindGroup,
+    occlusion_view: wgpu::TextureView,
+    enabled: bool
+}
+
+impl SsaoPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        depth_view: &wgpu::TextureView,
+        resolution: (u32, u32)
+    ) -> Self {
+        let parameters = EffectParameters::new(device, &[
+            ("radius", 0.5),
+            ("strength", 1.5),
+            ("bias", 0.025)
+        ], &["radius", "strength", "bias"]);
+
+        let texture_layout = |label| device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false }
+                    },
+                    count: None
+                }
+            ]
+        });
+        // depth formats can be bound as unfilterable float textures too
+        let depth_layout = texture_layout("Ssao Depth Bind Group Layout");
+        let raw_layout = texture_layout("Ssao Blur Bind Group Layout");
+
+        let create_pipeline = |bind_group_layouts: &[&wgpu::BindGroupLayout], source: &str, label| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts,
+                push_constant_ranges: &[]
+            });
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(
+                    format!("{}\n{}", include_str!("shaders/fullscreen.wgsl"), source).into()
+                )
+            };
+            pipeline::create_render_pipeline(
+                device, &layout, OCCLUSION_FORMAT, None, &[], shader, label, false, pipeline::DepthMode::Standard, 1
+            )
+        };
+        let pipeline = create_pipeline(
+            &[&depth_layout, camera_layout, &parameters.layout],
+            include_str!("shaders/ssao.wgsl"),
+            "Ssao Pipeline"
+        );
+        let blur_pipeline = create_pipeline(
+            &[&raw_layout, camera_layout],
+            include_str!("shaders/ssao_blur.wgsl"),
+            "Ssao Blur Pipeline"
+        );
+
+        let targets = create_targets(device, &depth_layout, &raw_layout, depth_view, resolution);
+
+        Self {
+            pipeline,
+            blur_pipeline,
+            parameters,
+            depth_layout,
+            raw_layout,
+            depth_bind_group: targets.depth_bind_group,
+            raw_view: targets.raw_view,
+            raw_bind_group: targets.raw_bind_group,
+            occlusion_view: targets.occlusion_view,
+            enabled: true
+        }
+    }
+
+    // the occlusion view changes too, so the environment bind group has
+    // to be recreated after this
+    pub fn resize(&mut self, device: &wgpu::Device, depth_view: &wgpu::TextureView, resolution: (u32, u32)) {
+        let targets = create_targets(device, &self.depth_layout, &self.raw_layout, depth_view, resolution);
+        self.depth_bind_group = targets.depth_bind_group;
+        self.raw_view = targets.raw_view;
+        self.raw_bind_group = targets.raw_bind_group;
+        self.occlusion_view = targets.occlusion_view;
+    }
+
+    // the ssao block of the settings file, radius is in world units and
+    // strength is the exponent applied to the visibility
+    pub fn configure(&mut self, settings: &Value) {
+        self.parameters.configure(settings);
+        if let Some(enabled) = settings["enabled"].as_bool() {
+            self.enabled = enabled;
+        }
+    }
+
+    pub fn toggle(&mut self) -> bool {
+        self.enabled = !self.enabled;
+        self.enabled
+    }
+
+    // what the environment bind group samples, white while disabled
+    pub fn occlusion_view(&self) -> &wgpu::TextureView {
+        &self.occlusion_view
+    }
+
+    // once a frame before the cameras render, unoccluded is all the lit
+    // passes see while ssao is disabled
+    pub fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Ssao Clear Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.occlusion_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store
+                }
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None
+        });
+    }
+
+    // has to run after the depth prepass and before the lit passes, once
+    // per camera with the camera's viewport so positions are rebuilt with
+    // the right projection
+    pub fn render(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        camera_bind_group: &wgpu::BindGroup,
+        viewport: (f32, f32, f32, f32)
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        self.parameters.upload(queue);
+        occlusion_pass(
+            encoder,
+            "Ssao Pass",
+            &self.pipeline,
+            &[&self.depth_bind_group, camera_bind_group, &self.parameters.bind_group],
+            &self.raw_view,
+            viewport
+        );
+        occlusion_pass(
+            encoder,
+            "Ssao Blur Pass",
+            &self.blur_pipeline,
+            &[&self.raw_bind_group, camera_bind_group],
+            &self.occlusion_view,
+            viewport
+        );
+    }
+}
+
+struct SsaoTargets {
+    depth_bind_group: wgpu::BindGroup,
+    raw_view: wgpu::TextureView,
+    raw_bind_group: wgpu::BindGroup,
+    occlusion_view: wgpu::TextureView
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    depth_layout: &wgpu::BindGroupLayout,
+    raw_layout: &wgpu::BindGroupLayout,
+    depth_view: &wgpu::TextureView,
+    resolution: (u32, u32)
+) -> SsaoTargets {
+    let create_view = |label| device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: resolution.0,
+            height: resolution.1,
+            depth_or_array_layers: 1
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: OCCLUSION_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[]
+    }).create_view(&wgpu::TextureViewDescriptor::default());
+    let raw_view = create_view("Ssao Raw Texture");
+    let occlusion_view = create_view("Ssao Occlusion Texture");
+
+    let create_bind_group = |layout, view, label| device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view)
+            }
+        ]
+    });
+
+    SsaoTargets {
+        depth_bind_group: create_bind_group(depth_layout, depth_view, "Ssao Depth Bind Group"),
+        raw_bind_group: create_bind_group(raw_layout, &raw_view, "Ssao Blur Bind Group"),
+        raw_view,
+        occlusion_view
+    }
+}
+
+// like the post processing passes, but limited to a viewport and keeping
+// whatever other cameras left outside it
+fn occlusion_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_groups: &[&wgpu::BindGroup],
+    output: &wgpu::TextureView,
+    viewport: (f32, f32, f32, f32)
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store
+            }
+        })],
+        depth_stencil_attachment: None,
+        occlusion_query_set: None,
+        timestamp_writes: None
+    });
+    let (x, y, width, height) = viewport;
+    render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+    render_pass.set_scissor_rect(x as u32, y as u32, width as u32, height as u32);
+    render_pass.set_pipeline(pipeline);
+    for (index, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(index as u32, bind_group, &[]);
+    }
+    render_pass.draw(0..3, 0..1);
+}
@@ -1,6 +1,9 @@
 use image::GenericImageView;
 use anyhow::*;
-use wgpu::util::DeviceExt;
+use super::{ibl, texture::f32_to_f16};
+
+// equirectangular panoramas are split into faces a quarter of their width
+const MAX_HDR_FACE_SIZE: u32 = 1024;
 
 pub struct Cubemap {
     #[allow(unused)]
@@ -93,83 +96,137 @@ impl Cubemap {
 
         Ok(Self { texture, view, sampler })
     }
+
+    pub fn from_hdr_bytes(
+        binary: &[u8],
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Self> {
+        let (dimensions, texels) = equirectangular_texels(binary, device.limits().max_texture_dimension_2d)?;
+
+        let size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        };
+        let source = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label: Some("Equirectangular Texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }
+        );
+        queue.write_texture(wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture: &source,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        bytemuck::cast_slice(&texels),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(8 * dimensions.0),
+            rows_per_image: Some(dimensions.1)
+        },
+        size);
+        let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let face_size = (dimensions.0 / 4).clamp(1, MAX_HDR_FACE_SIZE);
+        let texture = ibl::render_equirectangular(device, queue, &source_view, face_size);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Cubemap Texture View"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            array_layer_count: Some(6),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Nearest,
+                min_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Ok(Self { texture, view, sampler })
+    }
+}
+
+// half floats are filterable everywhere, anything brighter than their
+// range is clamped rather than turned into infinity. panoramas bigger than
+// a texture can be, like 16k hdris on most gpus, are scaled down first
+fn equirectangular_texels(binary: &[u8], max_dimension: u32) -> Result<((u32, u32), Vec<u16>)> {
+    // the generic image loader tone maps hdr files down to 8 bit srgb, the
+    // decoder itself gives the real radiance
+    let decoder = image::codecs::hdr::HdrDecoder::new(binary)?;
+    let (width, height) = (decoder.metadata().width, decoder.metadata().height);
+    let pixels = decoder.read_image_hdr()?;
+    let mut image = image::Rgba32FImage::from_raw(
+        width,
+        height,
+        pixels.iter().flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0]).collect()
+    ).ok_or_else(|| anyhow!("Hdr image smaller than its header says"))?;
+    if width > max_dimension || height > max_dimension {
+        let scale = max_dimension as f32 / width.max(height) as f32;
+        let scaled = |size: u32| ((size as f32 * scale) as u32).clamp(1, max_dimension);
+        let (scaled_width, scaled_height) = (scaled(width), scaled(height));
+        log::info!(
+            "Scaling a {}x{} panorama down to {}x{} to fit the texture size limit",
+            width, height, scaled_width, scaled_height
+        );
+        image = image::imageops::resize(&image, scaled_width, scaled_height, image::imageops::FilterType::Triangle);
+    }
+
+    let texels = image
+        .as_raw()
+        .iter()
+        .map(|value| f32_to_f16(value.min(65504.0)))
+        .collect();
+    Ok((image.dimensions(), texels))
 }
 
 pub struct CubemapComponent {
-    pub vertices: wgpu::Buffer,
+    pub cubemap: Cubemap,
     pub bind_group: wgpu::BindGroup
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct CubemapVertex {
-    pub position: [f32; 3]
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl CubemapVertex {
-    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
-        wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<CubemapVertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                }
-            ]
-        }
+    fn sample() -> Vec<u8> {
+        std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/res/skybox/sky.hdr")).unwrap()
+    }
+
+    #[test]
+    fn panorama_decodes_to_half_floats() {
+        let ((width, height), texels) = equirectangular_texels(&sample(), 8192).unwrap();
+        assert_eq!((width, height), (128, 64));
+        assert_eq!(texels.len(), 128 * 64 * 4);
+        assert!(texels.chunks(4).all(|texel| texel[3] == f32_to_f16(1.0)));
+        // the sun is far brighter than anything ldr could hold
+        assert!(texels.iter().any(|&texel| texel >= f32_to_f16(40.0)));
     }
-}
 
-pub fn create_cubemap_vertices(device: &wgpu::Device) -> wgpu::Buffer {
-    let vertices: &[CubemapVertex] = &[
-        CubemapVertex{ position:[-1.0,  1.0, -1.0] },
-        CubemapVertex{ position:[-1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0, -1.0] },
-        CubemapVertex{ position:[-1.0,  1.0, -1.0] },
-
-        CubemapVertex{ position:[-1.0, -1.0,  1.0] },
-        CubemapVertex{ position:[-1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[-1.0,  1.0, -1.0] },
-        CubemapVertex{ position:[-1.0,  1.0, -1.0] },
-        CubemapVertex{ position:[-1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[-1.0, -1.0,  1.0] },
-
-        CubemapVertex{ position:[ 1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0, -1.0] },
-
-        CubemapVertex{ position:[-1.0, -1.0,  1.0] },
-        CubemapVertex{ position:[-1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0,  1.0] },
-        CubemapVertex{ position:[-1.0, -1.0,  1.0] },
-
-        CubemapVertex{ position:[-1.0,  1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[-1.0,  1.0,  1.0] },
-        CubemapVertex{ position:[-1.0,  1.0, -1.0] },
-
-        CubemapVertex{ position:[-1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[-1.0, -1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0, -1.0] },
-        CubemapVertex{ position:[-1.0, -1.0,  1.0] },
-        CubemapVertex{ position:[ 1.0, -1.0,  1.0] }
-    ];
-
-    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some(&format!("Cubemap Vertex Buffer")),
-        contents: bytemuck::cast_slice(&vertices),
-        usage: wgpu::BufferUsages::VERTEX
-    })
+    #[test]
+    fn panorama_over_the_limit_is_scaled_down() {
+        let ((width, height), texels) = equirectangular_texels(&sample(), 32).unwrap();
+        assert_eq!((width, height), (32, 16));
+        assert_eq!(texels.len(), 32 * 16 * 4);
+    }
+
+    #[test]
+    fn panorama_that_isnt_hdr_is_an_error() {
+        assert!(equirectangular_texels(b"not an image", 8192).is_err());
+    }
 }
@@ -0,0 +1,498 @@
+use super::{
+    cubemap::Cubemap,
+    texture::f32_to_f16,
+    super::pipeline
+};
+
+const ENVIRONMENT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+const MAX_ENVIRONMENT_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+const PREFILTERED_MIPS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct FacePush {
+    face: u32,
+    roughness: f32,
+    source_resolution: f32,
+    padding: u32
+}
+
+// diffuse irradiance, specular prefiltered radiance and the brdf lut for
+// the split sum approximation, all derived from one environment cubemap
+pub struct Environment {
+    irradiance_view: wgpu::TextureView,
+    prefiltered_view: wgpu::TextureView,
+    brdf_lut_view: wgpu::TextureView,
+    sampler: wgpu::Sampler
+}
+
+impl Environment {
+    pub fn from_cubemap(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        cubemap: &Cubemap
+    ) -> Self {
+        let generator = Generator::new(device);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Environment Encoder")
+        });
+
+        // the skybox is copied into a mipmapped float cubemap first, the
+        // convolutions below read blurrier mips for sparse samples
+        let size = cubemap.texture.width().min(MAX_ENVIRONMENT_SIZE);
+        let mip_count = size.ilog2() + 1;
+        let environment = create_cube_texture(device, "Environment Texture", size, mip_count);
+        generator.render_cube(
+            device, &mut encoder, &generator.copy_pipeline, &cubemap.view, &environment, 0, FacePush::default()
+        );
+        for mip in 1..mip_count {
+            let previous_mip = environment.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                base_mip_level: mip - 1,
+                mip_level_count: Some(1),
+                ..Default::default()
+            });
+            generator.render_cube(
+                device, &mut encoder, &generator.copy_pipeline, &previous_mip, &environment, mip, FacePush::default()
+            );
+        }
+        let environment_view = environment.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        // the size is used to pick which mips the convolutions read from
+        let filter_push = FacePush {
+            source_resolution: size as f32,
+            ..Default::default()
+        };
+
+        let irradiance = create_cube_texture(device, "Irradiance Texture", IRRADIANCE_SIZE, 1);
+        generator.render_cube(
+            device, &mut encoder, &generator.irradiance_pipeline, &environment_view, &irradiance, 0, filter_push
+        );
+
+        let prefiltered = create_cube_texture(device, "Prefiltered Texture", PREFILTERED_SIZE, PREFILTERED_MIPS);
+        for mip in 0..PREFILTERED_MIPS {
+            let push = FacePush {
+                roughness: mip as f32 / (PREFILTERED_MIPS - 1) as f32,
+                ..filter_push
+            };
+            generator.render_cube(
+                device, &mut encoder, &generator.prefilter_pipeline, &environment_view, &prefiltered, mip, push
+            );
+        }
+
+        let brdf_lut = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Brdf Lut Texture"),
+            size: wgpu::Extent3d {
+                width: BRDF_LUT_SIZE,
+                height: BRDF_LUT_SIZE,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: BRDF_LUT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[]
+        });
+        let brdf_lut_view = brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Brdf Lut Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &brdf_lut_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store
+                    }
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None
+            });
+            render_pass.set_pipeline(&generator.brdf_pipeline);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let cube_view = |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let irradiance_view = cube_view(&irradiance);
+        let prefiltered_view = cube_view(&prefiltered);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            irradiance_view,
+            prefiltered_view,
+            brdf_lut_view,
+            sampler
+        }
+    }
+
+    // used when a scene has no skybox, a uniformly colored environment
+    // reproduces a constant ambient term
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: cgmath::Vector3<f32>
+    ) -> Self {
+        let texture = create_cube_texture(device, "Environment Color Texture", 1, 1);
+        let texel = [color.x, color.y, color.z, 1.0].map(f32_to_f16);
+        for layer in 0..6 {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x: 0, y: 0, z: layer }
+                },
+                bytemuck::cast_slice(&texel),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(8),
+                    rows_per_image: Some(1)
+                },
+                wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1
+                }
+            );
+        }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+        let cubemap = Cubemap { texture, view, sampler };
+
+        Self::from_cubemap(device, queue, &cubemap)
+    }
+
+    // the ambient occlusion from the ssao pass is bound next to the
+    // environment since it only ever scales the ambient term, the fog
+    // since it samples the prefiltered sky for its color
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        occlusion_view: &wgpu::TextureView,
+        fog_buffer: &wgpu::Buffer
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.irradiance_view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&self.prefiltered_view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&self.brdf_lut_view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(occlusion_view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: fog_buffer.as_entire_binding()
+                }
+            ]
+        })
+    }
+}
+
+// projects an equirectangular panorama onto the faces of a float cubemap,
+// hdr skyboxes are stored this way so they keep their full range
+pub fn render_equirectangular(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    source: &wgpu::TextureView,
+    size: u32
+) -> wgpu::Texture {
+    let bind_group_layout = device.create_bind_group_layout(
+        &wgpu::BindGroupLayoutDescriptor {
+        label: Some("Equirectangular Bind Group Layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                },
+                count: None
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None
+            }
+        ]
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Equirectangular Pipeline Layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[wgpu::PushConstantRange {
+            stages: wgpu::ShaderStages::FRAGMENT,
+            range: 0..std::mem::size_of::<FacePush>() as u32
+        }]
+    });
+    let shader = wgpu::ShaderModuleDescriptor {
+        label: Some("Equirectangular Shader"),
+        source: wgpu::ShaderSource::Wgsl(format!(
+            "{}\n{}",
+            include_str!("../shaders/ibl_common.wgsl"),
+            include_str!("../shaders/ibl_equirectangular.wgsl")
+        ).into())
+    };
+    let render_pipeline = pipeline::create_render_pipeline(
+        device, &layout, ENVIRONMENT_FORMAT, None, &[], shader, "Equirectangular Pipeline", false, pipeline::DepthMode::Standard, 1
+    );
+
+    // wraps around horizontally so the seam behind the camera is filtered
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Equirectangular Bind Group"),
+        layout: &bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source)
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&sampler)
+            }
+        ]
+    });
+
+    let texture = create_cube_texture(device, "Equirectangular Cubemap Texture", size, 1);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Equirectangular Encoder")
+    });
+    render_faces(&mut encoder, &render_pipeline, &bind_group, &texture, 0, FacePush::default());
+    queue.submit(std::iter::once(encoder.finish()));
+
+    texture
+}
+
+fn create_cube_texture(
+    device: &wgpu::Device,
+    label: &str,
+    size: u32,
+    mip_level_count: u32
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: ENVIRONMENT_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[]
+    })
+}
+
+// pipelines are only needed while an environment is being built
+struct Generator {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    copy_pipeline: wgpu::RenderPipeline,
+    irradiance_pipeline: wgpu::RenderPipeline,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    brdf_pipeline: wgpu::RenderPipeline
+}
+
+impl Generator {
+    fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+            label: Some("Environment Generator Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true }
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+        let push_constant_ranges = &[wgpu::PushConstantRange {
+            stages: wgpu::ShaderStages::FRAGMENT,
+            range: 0..std::mem::size_of::<FacePush>() as u32
+        }];
+        let cube_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Environment Generator Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges
+        });
+        let brdf_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Brdf Lut Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges
+        });
+
+        let common = include_str!("../shaders/ibl_common.wgsl");
+        let create_pipeline = |layout, format, source: &str, label| {
+            let shader = wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", common, source).into())
+            };
+            pipeline::create_render_pipeline(device, layout, format, None, &[], shader, label, false, pipeline::DepthMode::Standard, 1)
+        };
+        let copy_pipeline = create_pipeline(
+            &cube_layout, ENVIRONMENT_FORMAT, include_str!("../shaders/ibl_copy.wgsl"), "Environment Copy Pipeline"
+        );
+        let irradiance_pipeline = create_pipeline(
+            &cube_layout, ENVIRONMENT_FORMAT, include_str!("../shaders/ibl_irradiance.wgsl"), "Irradiance Pipeline"
+        );
+        let prefilter_pipeline = create_pipeline(
+            &cube_layout, ENVIRONMENT_FORMAT, include_str!("../shaders/ibl_prefilter.wgsl"), "Prefilter Pipeline"
+        );
+        let brdf_pipeline = create_pipeline(
+            &brdf_layout, BRDF_LUT_FORMAT, include_str!("../shaders/ibl_brdf.wgsl"), "Brdf Lut Pipeline"
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            bind_group_layout,
+            sampler,
+            copy_pipeline,
+            irradiance_pipeline,
+            prefilter_pipeline,
+            brdf_pipeline
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_cube(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        source: &wgpu::TextureView,
+        target: &wgpu::Texture,
+        mip: u32,
+        push: FacePush
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Environment Generator Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler)
+                }
+            ]
+        });
+        render_faces(encoder, pipeline, &bind_group, target, mip, push);
+    }
+}
+
+// renders all six faces of one mip level of a cube texture with a
+// fullscreen triangle each
+fn render_faces(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    target: &wgpu::Texture,
+    mip: u32,
+    push: FacePush
+) {
+    for face in 0..6 {
+        let view = target.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: mip,
+            mip_level_count: Some(1),
+            base_array_layer: face,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Environment Face Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store
+                }
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None
+        });
+        let push = FacePush { face, ..push };
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_push_constants(wgpu::ShaderStages::FRAGMENT, 0, bytemuck::cast_slice(&[push]));
+        render_pass.draw(0..3, 0..1);
+    }
+}
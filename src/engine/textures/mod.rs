@@ -1,2 +1,4 @@
 pub mod texture;
 pub mod cubemap;
+pub mod pbr;
+pub mod ibl;
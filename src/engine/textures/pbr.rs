@@ -0,0 +1,159 @@
+use anyhow::*;
+use wgpu::util::DeviceExt;
+
+use super::{
+    texture::Texture,
+    super::uniform::PbrMaterialUniform
+};
+
+// any texture left as None is replaced by a 1x1 texture that leaves the
+// matching factor in PbrMaterialUniform untouched
+#[derive(Default)]
+pub struct PbrTextures {
+    pub base_color: Option<Texture>,
+    pub metallic_roughness: Option<Texture>,
+    pub normal: Option<Texture>,
+    pub occlusion: Option<Texture>,
+    pub emissive: Option<Texture>
+}
+
+pub struct PbrMaterial {
+    pub bind_group: wgpu::BindGroup
+}
+
+impl PbrMaterial {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        textures: PbrTextures,
+        mut factors: PbrMaterialUniform,
+        label: &str
+    ) -> Result<Self> {
+        if textures.normal.is_none() {
+            factors.normal_scale = 0.0;
+        }
+        let white = [255, 255, 255, 255];
+        let base_color = match textures.base_color {
+            Some(texture) => texture,
+            None => Texture::from_color(device, queue, white, true, "Default Base Color Texture")?
+        };
+        let metallic_roughness = match textures.metallic_roughness {
+            Some(texture) => texture,
+            None => Texture::from_color(device, queue, white, false, "Default Metallic Roughness Texture")?
+        };
+        let normal = match textures.normal {
+            Some(texture) => texture,
+            None => Texture::from_color(device, queue, [128, 128, 255, 255], false, "Default Normal Texture")?
+        };
+        let occlusion = match textures.occlusion {
+            Some(texture) => texture,
+            None => Texture::from_color(device, queue, white, false, "Default Occlusion Texture")?
+        };
+        let emissive = match textures.emissive {
+            Some(texture) => texture,
+            None => Texture::from_color(device, queue, white, true, "Default Emissive Texture")?
+        };
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Pbr Material Buffer", label)),
+            contents: bytemuck::cast_slice(&[factors]),
+            usage: wgpu::BufferUsages::UNIFORM
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{:?} Pbr Material Bind Group", label)),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&base_color.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&metallic_roughness.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&normal.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&occlusion.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&emissive.view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&base_color.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::Sampler(&metallic_roughness.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::Sampler(&normal.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::Sampler(&occlusion.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::Sampler(&emissive.sampler)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        Ok(Self { bind_group })
+    }
+}
+
+// wrap and filter modes of a glTF sampler, textures loaded without one
+// repeat like the glTF default. filters the file leaves out stay nearest
+pub fn create_sampler(device: &wgpu::Device, sampler: Option<&gltf::texture::Sampler>) -> wgpu::Sampler {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat
+    };
+    let (wrap_s, wrap_t) = sampler.map_or((WrappingMode::Repeat, WrappingMode::Repeat), |s| (s.wrap_s(), s.wrap_t()));
+    let mag_filter = match sampler.and_then(|s| s.mag_filter()) {
+        Some(MagFilter::Linear) => wgpu::FilterMode::Linear,
+        _ => wgpu::FilterMode::Nearest
+    };
+    // the textures have no mip chain, so only the filter within a level matters
+    let min_filter = match sampler.and_then(|s| s.min_filter()) {
+        Some(MinFilter::Linear | MinFilter::LinearMipmapNearest | MinFilter::LinearMipmapLinear) => wgpu::FilterMode::Linear,
+        _ => wgpu::FilterMode::Nearest
+    };
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: sampler.and_then(|s| s.name()),
+        address_mode_u: address_mode(wrap_s),
+        address_mode_v: address_mode(wrap_t),
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter,
+        min_filter,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    })
+}
+
+// a model can carry several materials, each mesh indexes into this list
+pub struct PbrMaterialComponent {
+    pub materials: Vec<PbrMaterial>
+}
+
+impl PbrMaterialComponent {
+    pub fn material(&self, index: usize) -> &PbrMaterial {
+        &self.materials[index.min(self.materials.len() - 1)]
+    }
+}
@@ -19,6 +19,16 @@ impl Texture {
         Self::from_image(device, queue, &image, Some(label))
     }
 
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        srgb: bool,
+        label: &str
+    ) -> Result<Self> {
+        Self::from_rgba8(device, queue, &color, (1, 1), srgb, Some(label))
+    }
+
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -27,7 +37,24 @@ impl Texture {
     ) -> Result<Self> {
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
+        Self::from_rgba8(device, queue, &rgba, dimensions, true, label)
+    }
 
+    // data/linear textures (normal maps, metallic-roughness, ...) must not be
+    // created as srgb, or the sampled values get gamma decoded
+    pub fn from_rgba8(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &[u8],
+        dimensions: (u32, u32),
+        srgb: bool,
+        label: Option<&str>
+    ) -> Result<Self> {
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
@@ -40,7 +67,7 @@ impl Texture {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                format,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             }
@@ -53,7 +80,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -79,11 +106,14 @@ impl Texture {
     }
 
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    // the scene is lit in linear float color and tone mapped on upscale
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         //config: &wgpu::SurfaceConfiguration,
         resolution: (u32, u32),
+        sample_count: u32,
         label: &str
     ) -> Self {
         let size = wgpu::Extent3d {
@@ -93,14 +123,21 @@ impl Texture {
             height: resolution.1,
             depth_or_array_layers: 1
         };
+        // multisampled depth is only ever an attachment, gl can't put a
+        // sampled one in the same framebuffer as a multisampled color target
+        let usage = if sample_count > 1 {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        };
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage,
             view_formats: &[]
         };
         let texture = device.create_texture(&desc);
@@ -128,3 +165,105 @@ impl Texture {
 pub struct Material {
     pub bind_group: wgpu::BindGroup
 }
+
+// there's no half float type in std, this is enough to upload small
+// Rgba16Float textures from the cpu. rounds to nearest, ties to even
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // infinity stays infinity, nan stays nan
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // subnormal, shift the implicit leading one into the mantissa
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        return sign | round_to_even(half, mantissa & ((1 << shift) - 1), 1 << (shift - 1)) as u16;
+    }
+    // rounding may carry into the exponent, which is what we want, all the
+    // way up to infinity
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    sign | round_to_even(half, mantissa & 0x1fff, 0x1000) as u16
+}
+
+fn round_to_even(value: u32, remainder: u32, halfway: u32) -> u32 {
+    if remainder > halfway || (remainder == halfway && value & 1 == 1) {
+        value + 1
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16_zero_and_normals() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(0.5), 0x3800);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        // smallest normal
+        assert_eq!(f32_to_f16(2.0f32.powi(-14)), 0x0400);
+    }
+
+    #[test]
+    fn f16_subnormals() {
+        assert_eq!(f32_to_f16(2.0f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(1023.0 * 2.0f32.powi(-24)), 0x03ff);
+        assert_eq!(f32_to_f16(-3.0 * 2.0f32.powi(-24)), 0x8003);
+        // halfway to the smallest subnormal ties to zero, past it rounds up
+        assert_eq!(f32_to_f16(2.0f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_f16(2.0f32.powi(-25) * 1.01), 0x0001);
+        assert_eq!(f32_to_f16(1.5 * 2.0f32.powi(-24)), 0x0002);
+        assert_eq!(f32_to_f16(2.0f32.powi(-30)), 0x0000);
+        assert_eq!(f32_to_f16(-f32::MIN_POSITIVE), 0x8000);
+    }
+
+    #[test]
+    fn f16_overflow_to_infinity() {
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16(-1.0e6), 0xfc00);
+        assert_eq!(f32_to_f16(f32::MAX), 0x7c00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        // just under the rounding point stays finite
+        assert_eq!(f32_to_f16(65519.0), 0x7bff);
+    }
+
+    #[test]
+    fn f16_nan() {
+        for nan in [f32::NAN, -f32::NAN, f32::from_bits(0x7f80_0001)] {
+            let half = f32_to_f16(nan);
+            assert_eq!(half & 0x7c00, 0x7c00);
+            assert_ne!(half & 0x03ff, 0);
+        }
+    }
+
+    #[test]
+    fn f16_rounds_to_nearest_even() {
+        let ulp = 2.0f32.powi(-10);
+        assert_eq!(f32_to_f16(1.0 + ulp * 0.5), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + ulp * 1.5), 0x3c02);
+        assert_eq!(f32_to_f16(1.0 + ulp * 0.51), 0x3c01);
+        assert_eq!(f32_to_f16(1.0 + ulp * 0.49), 0x3c00);
+        // carries into the exponent
+        assert_eq!(f32_to_f16(2.0 - ulp * 0.5), 0x4000);
+    }
+}
@@ -0,0 +1,92 @@
+use serde_json::Value;
+use std::time::Duration;
+
+// simulation runs in whole ticks of the same length whatever the frame
+// rate, frames in between are drawn blended between the last two ticks
+pub struct FixedTimestep {
+    tick: Duration,
+    // after a long stall the ticks that don't fit are dropped, running
+    // every one of them would only make the next frame slower
+    max_catch_up_ticks: u32,
+    accumulator: Duration
+}
+
+impl FixedTimestep {
+    // the simulation block of the settings
+    pub fn new(settings: &Value) -> Self {
+        let tick_rate = settings["tick_rate"].as_f64().map_or(60.0, |rate| rate.max(1.0));
+        let max_catch_up_ticks = settings["max_catch_up_ticks"].as_u64().map_or(5, |ticks| ticks.max(1) as u32);
+
+        Self {
+            tick: Duration::from_secs_f64(1.0 / tick_rate),
+            max_catch_up_ticks,
+            accumulator: Duration::ZERO
+        }
+    }
+
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    // adds the frame time, returns how many ticks to run for it
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        self.accumulator += dt;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            if ticks == self.max_catch_up_ticks {
+                log::debug!("Simulation fell behind, dropping {:?}", self.accumulator);
+                // keeps the fraction so interpolation doesn't jump
+                self.accumulator = Duration::from_nanos((self.accumulator.as_nanos() % self.tick.as_nanos()) as u64);
+                break;
+            }
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    // how far the frame is between the last tick and the next, 0 to 1
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn settings_default_to_sixty_hertz() {
+        let timestep = FixedTimestep::new(&Value::Null);
+        assert!((timestep.tick().as_secs_f64() - 1.0 / 60.0).abs() < 1e-9);
+        assert_eq!(timestep.max_catch_up_ticks, 5);
+    }
+
+    #[test]
+    fn whole_ticks_run_and_the_remainder_carries_over() {
+        let mut timestep = FixedTimestep::new(&json!({ "tick_rate": 50 }));
+        let tick = timestep.tick();
+
+        assert_eq!(timestep.advance(tick / 2), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timestep.advance(tick / 2), 1);
+        assert!(timestep.alpha() < 1e-4);
+
+        assert_eq!(timestep.advance(tick * 3 + tick / 4), 3);
+        assert_eq!(timestep.accumulator, tick / 4);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn catch_up_is_clamped_and_keeps_the_fraction() {
+        let mut timestep = FixedTimestep::new(&json!({ "tick_rate": 50, "max_catch_up_ticks": 3 }));
+        let tick = timestep.tick();
+
+        assert_eq!(timestep.advance(tick * 10 + tick / 4), 3);
+        assert_eq!(timestep.accumulator, tick / 4);
+        // the dropped ticks don't come back on the next frame
+        assert_eq!(timestep.advance(tick / 2), 0);
+        assert!((timestep.alpha() - 0.75).abs() < 1e-4);
+    }
+}
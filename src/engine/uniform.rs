@@ -8,7 +8,17 @@ use super::camera::{
 pub struct CameraUniform {
     view_projection: [[f32; 4]; 4],
     view_without_translation: [[f32; 4]; 4],
-    projection: [[f32; 4]; 4]
+    projection: [[f32; 4]; 4],
+    view_position: [f32; 4],
+    // ssao rebuilds view space positions from the depth buffer
+    inverse_projection: [[f32; 4]; 4],
+    // the camera's viewport on the internal render target in pixels
+    viewport: [f32; 4],
+    // depth of the far plane, 0 with reverse z
+    far_depth: f32,
+    padding: u32,
+    // the sky is drawn through the perspective fov whatever the projection
+    sky_extent: [f32; 2]
 }
 
 impl CameraUniform {
@@ -17,17 +27,32 @@ impl CameraUniform {
         Self {
             view_projection: cgmath::Matrix4::identity().into(),
             view_without_translation: cgmath::Matrix4::identity().into(),
-            projection: cgmath::Matrix4::identity().into()
+            projection: cgmath::Matrix4::identity().into(),
+            view_position: [0.0; 4],
+            inverse_projection: cgmath::Matrix4::identity().into(),
+            viewport: [0.0; 4],
+            far_depth: 1.0,
+            padding: 0,
+            sky_extent: [1.0, 1.0]
         }
     }
 
+    pub fn update_viewport(&mut self, viewport: (f32, f32, f32, f32)) {
+        self.viewport = [viewport.0, viewport.1, viewport.2, viewport.3];
+    }
+
     pub fn update_view_projection(&mut self, camera: &Camera, projection: &Projection) {
+        use cgmath::SquareMatrix;
         let projection_matrix = projection.calculate_matrix();
         let mut view_matrix = camera.calculate_matrix();
         self.view_projection = (projection_matrix * view_matrix).into();
         view_matrix.w = cgmath::vec4(0.0, 0.0, 0.0, 1.0);
         self.view_without_translation = view_matrix.into();
         self.projection = projection_matrix.into();
+        self.inverse_projection = projection_matrix.invert().unwrap().into();
+        self.view_position = camera.position.to_homogeneous().into();
+        self.far_depth = projection.depth_mode().far_depth();
+        self.sky_extent = projection.sky_extent();
     }
 }
 
@@ -53,3 +78,88 @@ impl LightUniform {
         }
     }
 }
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    specular_color: [f32; 3],
+    shininess: f32
+}
+
+impl MaterialUniform {
+    pub fn new(
+        specular_color: cgmath::Vector3<f32>,
+        shininess: f32
+    ) -> Self {
+        Self {
+            specular_color: specular_color.into(),
+            shininess
+        }
+    }
+}
+
+// factors from the glTF metallic-roughness model, multiplied with the
+// matching textures in the pbr shader
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrMaterialUniform {
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub occlusion_strength: f32,
+    // zero disables normal mapping entirely
+    pub normal_scale: f32,
+    pub padding: u32
+}
+
+impl Default for PbrMaterialUniform {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            emissive_factor: [0.0, 0.0, 0.0],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            occlusion_strength: 1.0,
+            normal_scale: 0.0,
+            padding: 0
+        }
+    }
+}
+
+// distance fog modes plus an optional height fog layer, read from the
+// environment block of a scene
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    pub color: [f32; 3],
+    // 0 off, 1 linear, 2 exponential, 3 exponential squared
+    pub mode: u32,
+    pub density: f32,
+    pub start: f32,
+    pub end: f32,
+    // non zero samples the fog color from the skybox instead
+    pub from_skybox: u32,
+    // zero disables the height fog
+    pub height_density: f32,
+    pub height_falloff: f32,
+    pub height_base: f32,
+    pub sky_distance: f32
+}
+
+impl Default for FogUniform {
+    fn default() -> Self {
+        Self {
+            color: [0.5, 0.5, 0.5],
+            mode: 0,
+            density: 0.05,
+            start: 10.0,
+            end: 50.0,
+            from_skybox: 0,
+            height_density: 0.0,
+            height_falloff: 0.5,
+            height_base: 0.0,
+            sky_distance: 100.0
+        }
+    }
+}